@@ -0,0 +1,371 @@
+//! Checks run on the AST before evaluation, so programs with these errors don't run at all,
+//! and what closures capture, which is known from the AST alone
+
+use std::collections::{HashMap, HashSet};
+
+use crate::error::Error;
+use crate::parser::ast::*;
+use crate::NESTING_LIMIT;
+
+/// Finds every assignment to a variable that was not declared with `let mut`, or that a closure captured.
+/// Function arguments and `for` loop variables are immutable too, as in Rust.
+/// Variables that aren't declared at all are left for evaluation to report
+pub fn mutability(items: &[Spanned<Item>]) -> Vec<Error> {
+    let mut errs = Vec::new();
+    for item in items {
+        match &item.node {
+            Item::Function(function) => check_function(function, &mut errs),
+        }
+    }
+    errs
+}
+
+/// Whether a variable in scope can be assigned to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Binding {
+    /// Declared with `let mut`
+    Mutable,
+    /// Declared with `let`, or an argument or loop variable
+    Immutable,
+    /// Declared with `let mut` outside a closure that uses it, which only gets a copy of it
+    Captured,
+}
+
+/// How each variable in scope is bound, innermost scope last, following the same scoping rules as evaluation
+type Mutability = Vec<HashMap<String, Binding>>;
+
+/// Checks a function, which starts with only its arguments in scope
+fn check_function(function: &Function, errs: &mut Vec<Error>) {
+    // Functions can't see the variables of the blocks they are declared in
+    let args = function
+        .args
+        .iter()
+        .map(|arg| (arg.clone(), Binding::Immutable))
+        .collect();
+    let mut scopes = vec![args];
+    check_block(&function.body, &mut scopes, errs, 1);
+}
+
+/// Checks a block, in a scope of its own. `depth` is how many blocks and expressions in it is, as counted by evaluation.
+/// What's deeper than [NESTING_LIMIT] is never evaluated, so it isn't checked either, and long chains of operators,
+/// as in `1 + 1 + ... + 1`, don't overflow the stack
+fn check_block(block: &Block, scopes: &mut Mutability, errs: &mut Vec<Error>, depth: usize) {
+    if depth > NESTING_LIMIT {
+        return;
+    }
+    scopes.push(HashMap::new());
+    for statement in &block.0 {
+        check_statement(statement, scopes, errs, depth);
+    }
+    scopes.pop();
+}
+
+/// Checks a statement, declaring the variable it introduces if it's a `let`
+fn check_statement(
+    statement: &Spanned<Statement>,
+    scopes: &mut Mutability,
+    errs: &mut Vec<Error>,
+    depth: usize,
+) {
+    match &statement.node {
+        Statement::Null | Statement::Continue { .. } | Statement::Return(None) => (),
+        Statement::Break { value, .. } => {
+            if let Some(value) = value {
+                check_expr(value, scopes, errs, depth + 1);
+            }
+        }
+        Statement::Return(Some(expr)) | Statement::Expr(expr) => {
+            check_expr(expr, scopes, errs, depth + 1)
+        }
+        Statement::Item(item) => match item.as_ref() {
+            Item::Function(function) => check_function(function, errs),
+        },
+        Statement::Conditional {
+            r#if,
+            r#then,
+            r#else,
+        } => {
+            check_expr(r#if, scopes, errs, depth + 1);
+            check_block(r#then, scopes, errs, depth + 1);
+            if let Some(r#else) = r#else {
+                check_block(r#else, scopes, errs, depth + 1);
+            }
+        }
+        Statement::Let {
+            mutable,
+            lvalue,
+            rvalue,
+        } => {
+            // The value is checked before the variable exists, as `let x = x;` refers to the `x` it shadows
+            check_expr(rvalue, scopes, errs, depth + 1);
+            if let Some(scope) = scopes.last_mut() {
+                let binding = match mutable {
+                    true => Binding::Mutable,
+                    false => Binding::Immutable,
+                };
+                scope.insert(lvalue.clone(), binding);
+            }
+        }
+        Statement::Assign { lvalue, rvalue, .. } => {
+            check_expr(rvalue, scopes, errs, depth + 1);
+            let mut place = lvalue;
+            let name = loop {
+                match &place.node {
+                    Place::Var(name) => break name,
+                    Place::Index(array, index) => {
+                        check_expr(index, scopes, errs, depth + 1);
+                        place = array;
+                    }
+                }
+            };
+            let name = name.clone();
+            let span = lvalue.span.clone();
+            match scopes.iter().rev().find_map(|scope| scope.get(&name)) {
+                Some(Binding::Immutable) => errs.push(Error::AssignToImmutable { name, span }),
+                Some(Binding::Captured) => errs.push(Error::AssignToCaptured { name, span }),
+                Some(Binding::Mutable) | None => (),
+            }
+        }
+        Statement::Block(block) => check_block(block, scopes, errs, depth + 1),
+        Statement::Loop(r#loop) => check_block(&r#loop.body, scopes, errs, depth + 1),
+        Statement::While(r#while) => {
+            check_expr(&r#while.cond, scopes, errs, depth + 1);
+            check_block(&r#while.body, scopes, errs, depth + 1);
+        }
+        Statement::For(r#for) => {
+            check_expr(&r#for.iter, scopes, errs, depth + 1);
+            scopes.push(HashMap::from([(r#for.var.clone(), Binding::Immutable)]));
+            check_block(&r#for.body, scopes, errs, depth + 1);
+            scopes.pop();
+        }
+    }
+}
+
+/// Checks the blocks nested in an expression, as in a [Loop]
+fn check_expr(expr: &Spanned<Expr>, scopes: &mut Mutability, errs: &mut Vec<Error>, depth: usize) {
+    if depth > NESTING_LIMIT {
+        return;
+    }
+    match &expr.node {
+        Expr::Literal(_) | Expr::Var(_) => (),
+        Expr::Neg(a) | Expr::Not(a) | Expr::Cast(a, _) => check_expr(a, scopes, errs, depth + 1),
+        Expr::Add(a, b)
+        | Expr::Sub(a, b)
+        | Expr::Mul(a, b)
+        | Expr::Div(a, b)
+        | Expr::Rem(a, b)
+        | Expr::Pow(a, b)
+        | Expr::BitAnd(a, b)
+        | Expr::BitOr(a, b)
+        | Expr::BitXor(a, b)
+        | Expr::Shl(a, b)
+        | Expr::Shr(a, b)
+        | Expr::Lt(a, b)
+        | Expr::Gt(a, b)
+        | Expr::Le(a, b)
+        | Expr::Ge(a, b)
+        | Expr::Eq(a, b)
+        | Expr::Ne(a, b)
+        | Expr::And(a, b)
+        | Expr::Or(a, b)
+        | Expr::Range(a, b)
+        | Expr::RangeInclusive(a, b)
+        | Expr::Index(a, b) => {
+            check_expr(a, scopes, errs, depth + 1);
+            check_expr(b, scopes, errs, depth + 1);
+        }
+        Expr::Array(exprs) => {
+            for expr in exprs {
+                check_expr(expr, scopes, errs, depth + 1);
+            }
+        }
+        Expr::Call(callee, args) => {
+            check_expr(callee, scopes, errs, depth + 1);
+            for arg in args {
+                check_expr(arg, scopes, errs, depth + 1);
+            }
+        }
+        Expr::Macro { args, .. } => {
+            for arg in args {
+                check_expr(arg, scopes, errs, depth + 1);
+            }
+        }
+        Expr::MethodCall { receiver, args, .. } => {
+            check_expr(receiver, scopes, errs, depth + 1);
+            for arg in args {
+                check_expr(arg, scopes, errs, depth + 1);
+            }
+        }
+        Expr::Closure { args, body, .. } => {
+            // Closures get copies of the variables around them, so changing those would change nothing.
+            // From the outermost scope in, so inner bindings replace the ones they shadow
+            let mut captured: HashMap<_, _> = scopes
+                .iter()
+                .flatten()
+                .map(|(name, binding)| match binding {
+                    Binding::Mutable | Binding::Captured => (name.clone(), Binding::Captured),
+                    Binding::Immutable => (name.clone(), Binding::Immutable),
+                })
+                .collect();
+            captured.extend(args.iter().map(|arg| (arg.clone(), Binding::Immutable)));
+            check_block(body, &mut vec![captured], errs, 1);
+        }
+        Expr::Loop(r#loop) => check_block(&r#loop.body, scopes, errs, depth + 1),
+    }
+}
+
+/// Variables the closure with `args` and `body` uses from around it, which are the ones it captures.
+/// Names called as functions are included too, as a variable with that name would be called instead
+pub fn free_variables(args: &[String], body: &Block) -> Vec<String> {
+    let mut free = HashSet::new();
+    let mut bound = vec![args.iter().cloned().collect()];
+    free_in_block(body, &mut bound, &mut free, 1);
+    free.into_iter().collect()
+}
+
+/// Names declared in each scope, innermost scope last
+type Bound = Vec<HashSet<String>>;
+
+/// Adds `name` to the `free` variables if no scope declares it
+fn use_variable(name: &str, bound: &Bound, free: &mut HashSet<String>) {
+    if !bound.iter().any(|scope| scope.contains(name)) {
+        free.insert(name.to_string());
+    }
+}
+
+/// Finds the free variables of a block, in a scope of its own.
+/// As with [check_block()], what's deeper than [NESTING_LIMIT] is never evaluated, so it captures nothing
+fn free_in_block(block: &Block, bound: &mut Bound, free: &mut HashSet<String>, depth: usize) {
+    if depth > NESTING_LIMIT {
+        return;
+    }
+    bound.push(HashSet::new());
+    for statement in &block.0 {
+        free_in_statement(statement, bound, free, depth);
+    }
+    bound.pop();
+}
+
+/// Finds the free variables of a statement, declaring the variable it introduces if it's a `let`
+fn free_in_statement(
+    statement: &Spanned<Statement>,
+    bound: &mut Bound,
+    free: &mut HashSet<String>,
+    depth: usize,
+) {
+    match &statement.node {
+        // Functions can't see the variables around them
+        Statement::Null
+        | Statement::Continue { .. }
+        | Statement::Return(None)
+        | Statement::Break { value: None, .. }
+        | Statement::Item(_) => (),
+        Statement::Break {
+            value: Some(expr), ..
+        }
+        | Statement::Return(Some(expr))
+        | Statement::Expr(expr) => free_in_expr(expr, bound, free, depth + 1),
+        Statement::Conditional {
+            r#if,
+            r#then,
+            r#else,
+        } => {
+            free_in_expr(r#if, bound, free, depth + 1);
+            free_in_block(r#then, bound, free, depth + 1);
+            if let Some(r#else) = r#else {
+                free_in_block(r#else, bound, free, depth + 1);
+            }
+        }
+        Statement::Let { lvalue, rvalue, .. } => {
+            free_in_expr(rvalue, bound, free, depth + 1);
+            if let Some(scope) = bound.last_mut() {
+                scope.insert(lvalue.clone());
+            }
+        }
+        Statement::Assign { lvalue, rvalue, .. } => {
+            free_in_expr(rvalue, bound, free, depth + 1);
+            let mut place = lvalue;
+            loop {
+                match &place.node {
+                    Place::Var(name) => break use_variable(name, bound, free),
+                    Place::Index(array, index) => {
+                        free_in_expr(index, bound, free, depth + 1);
+                        place = array;
+                    }
+                }
+            }
+        }
+        Statement::Block(block) => free_in_block(block, bound, free, depth + 1),
+        Statement::Loop(r#loop) => free_in_block(&r#loop.body, bound, free, depth + 1),
+        Statement::While(r#while) => {
+            free_in_expr(&r#while.cond, bound, free, depth + 1);
+            free_in_block(&r#while.body, bound, free, depth + 1);
+        }
+        Statement::For(r#for) => {
+            free_in_expr(&r#for.iter, bound, free, depth + 1);
+            bound.push(HashSet::from([r#for.var.clone()]));
+            free_in_block(&r#for.body, bound, free, depth + 1);
+            bound.pop();
+        }
+    }
+}
+
+/// Finds the free variables of an expression
+fn free_in_expr(expr: &Spanned<Expr>, bound: &mut Bound, free: &mut HashSet<String>, depth: usize) {
+    if depth > NESTING_LIMIT {
+        return;
+    }
+    match &expr.node {
+        Expr::Literal(_) => (),
+        Expr::Var(name) => use_variable(name, bound, free),
+        // What a nested closure captures was found when it was parsed
+        Expr::Closure { captures, .. } => {
+            for name in captures {
+                use_variable(name, bound, free);
+            }
+        }
+        Expr::Neg(a) | Expr::Not(a) | Expr::Cast(a, _) => free_in_expr(a, bound, free, depth + 1),
+        Expr::Add(a, b)
+        | Expr::Sub(a, b)
+        | Expr::Mul(a, b)
+        | Expr::Div(a, b)
+        | Expr::Rem(a, b)
+        | Expr::Pow(a, b)
+        | Expr::BitAnd(a, b)
+        | Expr::BitOr(a, b)
+        | Expr::BitXor(a, b)
+        | Expr::Shl(a, b)
+        | Expr::Shr(a, b)
+        | Expr::Lt(a, b)
+        | Expr::Gt(a, b)
+        | Expr::Le(a, b)
+        | Expr::Ge(a, b)
+        | Expr::Eq(a, b)
+        | Expr::Ne(a, b)
+        | Expr::And(a, b)
+        | Expr::Or(a, b)
+        | Expr::Range(a, b)
+        | Expr::RangeInclusive(a, b)
+        | Expr::Index(a, b) => {
+            free_in_expr(a, bound, free, depth + 1);
+            free_in_expr(b, bound, free, depth + 1);
+        }
+        Expr::Array(exprs) | Expr::Macro { args: exprs, .. } => {
+            for expr in exprs {
+                free_in_expr(expr, bound, free, depth + 1);
+            }
+        }
+        Expr::Call(callee, args)
+        | Expr::MethodCall {
+            receiver: callee,
+            args,
+            ..
+        } => {
+            free_in_expr(callee, bound, free, depth + 1);
+            for arg in args {
+                free_in_expr(arg, bound, free, depth + 1);
+            }
+        }
+        Expr::Loop(r#loop) => free_in_block(&r#loop.body, bound, free, depth + 1),
+    }
+}
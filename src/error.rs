@@ -0,0 +1,375 @@
+//! Errors reported while parsing or evaluating ZECA source
+
+use std::fmt;
+
+use chumsky::error::{Simple, SimpleReason};
+
+/// A range of characters in the source. Same as used by [Chumsky's errors][chumsky::error::Simple::span()]
+pub type Span = std::ops::Range<usize>;
+
+/// Everything that can go wrong with a ZECA program. Every variant carries the [Span] of the source that caused it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// Source does not match the grammar
+    Parse {
+        /// Where the unexpected input is
+        span: Span,
+        /// What would have been accepted instead. `None` stands for the end of input
+        expected: Vec<Option<char>>,
+        /// What was found instead. `None` stands for the end of input
+        found: Option<char>,
+        /// Name of the construct being parsed, if any
+        label: Option<&'static str>,
+    },
+    /// Source matches the grammar, but is still invalid, as in a literal that can't be represented
+    InvalidSyntax {
+        /// Where the invalid construct is
+        span: Span,
+        /// What is wrong with it
+        message: String,
+    },
+    /// There is no `main` function to start evaluation from
+    NoMain {
+        /// The whole program
+        span: Span,
+    },
+    /// Variable not declared in any reachable scope
+    UndefinedVariable {
+        /// Name of the variable
+        name: String,
+        /// Where the variable is used
+        span: Span,
+    },
+    /// Assignment to a variable not declared with `let mut`
+    AssignToImmutable {
+        /// Name of the variable
+        name: String,
+        /// Where the assignment is
+        span: Span,
+    },
+    /// Assignment inside a closure to a mutable variable from outside it, which the closure only has a copy of
+    AssignToCaptured {
+        /// Name of the variable
+        name: String,
+        /// Where the assignment is
+        span: Span,
+    },
+    /// Function not declared
+    UndefinedFunction {
+        /// Name of the function
+        name: String,
+        /// Where the function is called
+        span: Span,
+    },
+    /// Method that doesn't exist for the type of the value it is called on
+    UndefinedMethod {
+        /// Name of the method
+        name: String,
+        /// Type of the value the method is called on. See [Literal::type_name()][crate::parser::ast::Literal::type_name()]
+        type_name: String,
+        /// Where the method name is
+        span: Span,
+    },
+    /// Function or method called with the wrong number of arguments
+    ArityMismatch {
+        /// Name of the function or method
+        name: String,
+        /// Number of arguments the function declares
+        expected: usize,
+        /// Number of arguments passed in the call
+        found: usize,
+        /// Where the function is called
+        span: Span,
+    },
+    /// Value of an unexpected type, like `-"string"` or `if 1 {}`
+    TypeMismatch {
+        /// Description of the accepted types
+        expected: String,
+        /// Type of the value found. See [Literal::type_name()][crate::parser::ast::Literal::type_name()]
+        found: String,
+        /// Where the value comes from
+        span: Span,
+    },
+    /// Indexing past the end of an array or a `str`
+    IndexOutOfBounds {
+        /// Index used
+        index: usize,
+        /// Length of the array, or number of `char`s in the `str`
+        len: usize,
+        /// Where the indexing is
+        span: Span,
+    },
+    /// Slicing with a range that goes past the end of an array or a `str`, or that ends before it starts
+    SliceOutOfBounds {
+        /// Start of the range
+        start: usize,
+        /// End of the range, exclusive
+        end: usize,
+        /// Length of the array, or number of `char`s in the `str`
+        len: usize,
+        /// Where the slicing is
+        span: Span,
+    },
+    /// Indexing with a negative integer
+    NegativeIndex {
+        /// Where the index is
+        span: Span,
+    },
+    /// `str` that can't be turned into a value with `parse()`
+    UnparsableValue {
+        /// The `str` being parsed
+        text: String,
+        /// Where `parse()` is called
+        span: Span,
+    },
+    /// Width or precision of a formatting macro, taken from an argument, that is too large
+    CountTooLarge {
+        /// The width or precision
+        count: usize,
+        /// Largest width or precision allowed
+        limit: usize,
+        /// Where the argument is
+        span: Span,
+    },
+    /// Integer division by zero
+    DivisionByZero {
+        /// Where the division is
+        span: Span,
+    },
+    /// `break` used outside of any loop
+    BreakOutsideLoop {
+        /// Where the `break` is
+        span: Span,
+    },
+    /// `continue` used outside of any loop
+    ContinueOutsideLoop {
+        /// Where the `continue` is
+        span: Span,
+    },
+    /// `break 'label` or `continue 'label` with no enclosing loop called `'label`
+    UndeclaredLabel {
+        /// Name of the label, without the leading `'`
+        label: String,
+        /// Where the label is used
+        span: Span,
+    },
+    /// `break value` out of a loop that is not a `loop`, which can't evaluate to a value
+    BreakWithValue {
+        /// Where the `break` is
+        span: Span,
+    },
+    /// Integer arithmetic with a result that doesn't fit the integer type
+    Overflow {
+        /// The arithmetic operation, as in `"add"` or `"negate"`
+        operation: &'static str,
+        /// Where the operation is
+        span: Span,
+    },
+    /// Exponent or shift amount that isn't a `u32`, as with negative ones
+    OperandOutOfRange {
+        /// What the operand is, as in `"exponent"`
+        operand: &'static str,
+        /// The value of the operand
+        value: String,
+        /// Where the operand is
+        span: Span,
+    },
+    /// Too many nested function calls, usually from a recursion that never stops
+    RecursionLimit {
+        /// Maximum depth of nested calls
+        limit: usize,
+        /// Where the call that went over the limit is
+        span: Span,
+    },
+    /// Too many nested blocks and expressions being evaluated at once, recursive calls included
+    NestingLimit {
+        /// Maximum depth of nested blocks and expressions
+        limit: usize,
+        /// Where the block or expression that went over the limit is
+        span: Span,
+    },
+}
+
+impl Error {
+    /// Where in the source the error happened
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Parse { span, .. }
+            | Self::InvalidSyntax { span, .. }
+            | Self::NoMain { span }
+            | Self::UndefinedVariable { span, .. }
+            | Self::AssignToImmutable { span, .. }
+            | Self::AssignToCaptured { span, .. }
+            | Self::UndefinedFunction { span, .. }
+            | Self::UndefinedMethod { span, .. }
+            | Self::ArityMismatch { span, .. }
+            | Self::TypeMismatch { span, .. }
+            | Self::IndexOutOfBounds { span, .. }
+            | Self::SliceOutOfBounds { span, .. }
+            | Self::NegativeIndex { span }
+            | Self::UnparsableValue { span, .. }
+            | Self::CountTooLarge { span, .. }
+            | Self::DivisionByZero { span }
+            | Self::BreakOutsideLoop { span }
+            | Self::ContinueOutsideLoop { span }
+            | Self::UndeclaredLabel { span, .. }
+            | Self::BreakWithValue { span }
+            | Self::Overflow { span, .. }
+            | Self::OperandOutOfRange { span, .. }
+            | Self::RecursionLimit { span, .. }
+            | Self::NestingLimit { span, .. } => span.clone(),
+        }
+    }
+}
+
+/// Formats a token for messages, with `None` being the end of input
+pub(crate) fn token(token: &Option<char>) -> String {
+    match token {
+        Some(c) => format!("`{}`", c),
+        None => "end of input".to_string(),
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse {
+                expected,
+                found,
+                label,
+                ..
+            } => {
+                write!(f, "unexpected {}", token(found))?;
+                if let Some(label) = label {
+                    write!(f, " while parsing {}", label)?;
+                }
+                match expected.as_slice() {
+                    [] => Ok(()),
+                    [expected] => write!(f, ", expected {}", token(expected)),
+                    expected => write!(
+                        f,
+                        ", expected one of {}",
+                        expected.iter().map(token).collect::<Vec<_>>().join(", ")
+                    ),
+                }
+            }
+            Self::InvalidSyntax { message, .. } => write!(f, "{}", message),
+            Self::NoMain { .. } => write!(f, "no function named `main` in top-level items"),
+            Self::UndefinedVariable { name, .. } => {
+                write!(f, "cannot find variable `{}` in this scope", name)
+            }
+            Self::AssignToImmutable { name, .. } => {
+                write!(f, "cannot assign twice to immutable variable `{}`", name)
+            }
+            Self::AssignToCaptured { name, .. } => write!(
+                f,
+                "cannot assign to captured variable `{}`; closures capture by value",
+                name
+            ),
+            Self::UndefinedFunction { name, .. } => {
+                write!(f, "cannot find function `{}` in this scope", name)
+            }
+            Self::UndefinedMethod {
+                name, type_name, ..
+            } => write!(
+                f,
+                "no method named `{}` found for `{}` in the current scope",
+                name, type_name
+            ),
+            Self::ArityMismatch {
+                name,
+                expected,
+                found,
+                ..
+            } => write!(
+                f,
+                "function `{}` takes {} argument{} but {} {} supplied",
+                name,
+                expected,
+                if *expected == 1 { "" } else { "s" },
+                found,
+                if *found == 1 { "was" } else { "were" },
+            ),
+            Self::TypeMismatch {
+                expected, found, ..
+            } => write!(
+                f,
+                "mismatched types: expected {}, found `{}`",
+                expected, found
+            ),
+            Self::IndexOutOfBounds { index, len, .. } => write!(
+                f,
+                "index out of bounds: the len is {} but the index is {}",
+                len, index
+            ),
+            Self::SliceOutOfBounds {
+                start, end, len, ..
+            } if start > end => write!(
+                f,
+                "slice index starts at {} but ends at {}, for a value of length {}",
+                start, end, len
+            ),
+            Self::SliceOutOfBounds { end, len, .. } => write!(
+                f,
+                "range end index {} out of range for a value of length {}",
+                end, len
+            ),
+            Self::NegativeIndex { .. } => write!(f, "cannot index with a negative integer"),
+            Self::UnparsableValue { text, .. } => {
+                write!(f, "cannot parse {:?} into a number or `bool`", text)
+            }
+            Self::CountTooLarge { count, limit, .. } => write!(
+                f,
+                "width or precision {} is over the limit of {}",
+                count, limit
+            ),
+            Self::DivisionByZero { .. } => write!(f, "attempt to divide by zero"),
+            Self::BreakOutsideLoop { .. } => write!(f, "`break` outside of a loop"),
+            Self::ContinueOutsideLoop { .. } => write!(f, "`continue` outside of a loop"),
+            Self::UndeclaredLabel { label, .. } => {
+                write!(f, "use of undeclared label `'{}`", label)
+            }
+            Self::BreakWithValue { .. } => {
+                write!(f, "`break` with value is only allowed inside `loop`")
+            }
+            Self::Overflow { operation, .. } => {
+                write!(f, "attempt to {} with overflow", operation)
+            }
+            Self::OperandOutOfRange { operand, value, .. } => {
+                write!(f, "{} `{}` is out of range for `u32`", operand, value)
+            }
+            Self::RecursionLimit { limit, .. } => {
+                write!(f, "reached the recursion limit of {} nested calls", limit)
+            }
+            Self::NestingLimit { limit, .. } => write!(
+                f,
+                "reached the limit of {} nested blocks and expressions",
+                limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Simple<char>> for Error {
+    fn from(err: Simple<char>) -> Self {
+        match err.reason() {
+            SimpleReason::Custom(message) => Self::InvalidSyntax {
+                span: err.span(),
+                message: message.clone(),
+            },
+            SimpleReason::Unexpected | SimpleReason::Unclosed { .. } => {
+                let mut expected: Vec<_> = err.expected().copied().collect();
+                // Expected tokens come out of a set, so they are sorted for stable messages
+                expected.sort();
+                Self::Parse {
+                    span: err.span(),
+                    expected,
+                    found: err.found().copied(),
+                    label: err.label(),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! Formatting of the arguments of formatting macros, as in `format!("{:>8.2}", x)`
+
+use crate::error::{Error, Span};
+use crate::parser::ast::*;
+use crate::{type_mismatch, Unwind};
+
+/// Formats the `pieces` of a format string, with the already evaluated arguments of the macro
+pub(crate) fn format(pieces: &[FormatPiece], args: &[Spanned<Literal>]) -> Result<String, Unwind> {
+    let mut output = String::new();
+    for piece in pieces {
+        match piece {
+            FormatPiece::Text(text) => output.push_str(text),
+            FormatPiece::Arg { arg, spec } => {
+                let width = spec.width.map(|width| count(width, args)).transpose()?;
+                let precision = spec
+                    .precision
+                    .map(|precision| count(precision, args))
+                    .transpose()?;
+                // The parser only refers to arguments that exist
+                if let Some(arg) = args.get(*arg) {
+                    output.push_str(&format_arg(&arg.node, spec, width, precision));
+                }
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// Value of a width or a precision, which must be a `usize` no greater than [MAX_COUNT] if it comes from an argument
+fn count(count: Count, args: &[Spanned<Literal>]) -> Result<usize, Unwind> {
+    let arg = match count {
+        Count::Is(n) => return Ok(n),
+        // As with the arguments themselves, the parser only refers to arguments that exist
+        Count::Arg(arg) => match args.get(arg) {
+            Some(arg) => arg,
+            None => return Ok(0),
+        },
+    };
+    let not_usize = |span: &Span| type_mismatch("a `usize`", &arg.node, span);
+    match &arg.node {
+        Literal::Num(n) if !n.number_type().is_float() => match n.try_cast(NumberType::UInteger) {
+            Some(Number::UInteger(n)) if n <= MAX_COUNT => Ok(n),
+            Some(Number::UInteger(n)) => Err(Error::CountTooLarge {
+                count: n,
+                limit: MAX_COUNT,
+                span: arg.span.clone(),
+            }
+            .into()),
+            _ => Err(not_usize(&arg.span)),
+        },
+        _ => Err(not_usize(&arg.span)),
+    }
+}
+
+/// Formats a single argument as `spec` says, with its `width` and `precision` already evaluated
+fn format_arg(
+    value: &Literal,
+    spec: &FormatSpec,
+    width: Option<usize>,
+    precision: Option<usize>,
+) -> String {
+    let mut text = match (spec.debug, precision) {
+        (false, None) => value.to_string(),
+        (false, Some(precision)) => format!("{:.*}", precision, value),
+        (true, None) => value.debug().to_string(),
+        (true, Some(precision)) => format!("{:.*}", precision, value.debug()),
+    };
+    let is_number = matches!(value, Literal::Num(_));
+    if spec.sign && is_number && !text.starts_with('-') {
+        text.insert(0, '+');
+    }
+
+    let padding = match width {
+        Some(width) => width.saturating_sub(text.chars().count()),
+        None => 0,
+    };
+    if spec.zero && is_number {
+        // Zeros go between the sign and the digits
+        let sign = if text.starts_with(['+', '-']) { 1 } else { 0 };
+        text.insert_str(sign, &"0".repeat(padding));
+        return text;
+    }
+    let fill = |len| spec.fill.to_string().repeat(len);
+    let align = spec.align.unwrap_or(match is_number {
+        true => Align::Right,
+        false => Align::Left,
+    });
+    match align {
+        Align::Left => text + &fill(padding),
+        Align::Center => fill(padding / 2) + &text + &fill(padding - padding / 2),
+        Align::Right => fill(padding) + &text,
+    }
+}
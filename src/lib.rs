@@ -10,133 +10,656 @@
 #[cfg(test)]
 mod unittest;
 
+pub mod check;
+pub mod error;
+mod format;
+mod methods;
 pub mod parser;
+pub mod report;
 
-use std::collections::HashMap;
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    io::{self, Write},
+    sync::Arc,
+};
 
 use chumsky::{prelude::end, text::TextParser, Parser};
+use error::{Error, Span};
 use parser::ast::*;
 
+/// Applies `$clj` to two numbers, after [unifying][unify()] their types.
+/// Returns a type mismatch from the enclosing function for anything else, pointing at the operand expressions `$a` or `$b`
 macro_rules! for_every_number_Value {
-    ($expr:expr, $clj:expr) => {
+    ($expr:expr, ($a:expr, $b:expr), $clj:expr) => {
         match $expr {
-            (Literal::Num(n), Literal::Num(o)) => match (n, o) {
+            (Literal::Num(n), Literal::Num(o)) => match unify(n, o, &$a.span, &$b.span)? {
                 (Number::Integer(x), Number::Integer(y)) => $clj(x, y),
-                // (Number::UInteger(x), Number::UInteger(y)) => $clj(x, y),
+                (Number::Isize(x), Number::Isize(y)) => $clj(x, y),
+                (Number::UInteger(x), Number::UInteger(y)) => $clj(x, y),
+                (Number::I8(x), Number::I8(y)) => $clj(x, y),
+                (Number::I16(x), Number::I16(y)) => $clj(x, y),
+                (Number::I32(x), Number::I32(y)) => $clj(x, y),
+                (Number::I64(x), Number::I64(y)) => $clj(x, y),
+                (Number::U8(x), Number::U8(y)) => $clj(x, y),
+                (Number::U16(x), Number::U16(y)) => $clj(x, y),
+                (Number::U32(x), Number::U32(y)) => $clj(x, y),
+                (Number::U64(x), Number::U64(y)) => $clj(x, y),
+                // NaNs compare as in Rust: equal to nothing, not even themselves, and neither less nor greater than anything
                 (Number::Float(x), Number::Float(y)) => $clj(x, y),
-                _ => unimplemented!(),
+                (Number::F64(x), Number::F64(y)) => $clj(x, y),
+                (Number::F32(x), Number::F32(y)) => $clj(x, y),
+                (n, o) => return Err(number_mismatch(n, o, &$b.span)),
             },
-            _ => unimplemented!(),
+            (Literal::Num(_), value) => return Err(type_mismatch("a number", &value, &$b.span)),
+            (value, _) => return Err(type_mismatch("a number", &value, &$a.span)),
         }
     };
 }
 
+/// Same as [`for_every_number_Value!()`], with the `$checked` method used on integers instead, so overflows give `None`
 macro_rules! for_every_number_Value_wrapped {
-    ($expr:expr, $clj:expr) => {
+    ($expr:expr, ($a:expr, $b:expr), $checked:ident, $clj:expr) => {
         match $expr {
-            (Literal::Num(n), Literal::Num(o)) => match (n, o) {
+            (Literal::Num(n), Literal::Num(o)) => match unify(n, o, &$a.span, &$b.span)? {
+                (Number::Integer(x), Number::Integer(y)) => x.$checked(y).map(Number::Integer),
+                (Number::Isize(x), Number::Isize(y)) => x.$checked(y).map(Number::Isize),
+                (Number::UInteger(x), Number::UInteger(y)) => x.$checked(y).map(Number::UInteger),
+                (Number::I8(x), Number::I8(y)) => x.$checked(y).map(Number::I8),
+                (Number::I16(x), Number::I16(y)) => x.$checked(y).map(Number::I16),
+                (Number::I32(x), Number::I32(y)) => x.$checked(y).map(Number::I32),
+                (Number::I64(x), Number::I64(y)) => x.$checked(y).map(Number::I64),
+                (Number::U8(x), Number::U8(y)) => x.$checked(y).map(Number::U8),
+                (Number::U16(x), Number::U16(y)) => x.$checked(y).map(Number::U16),
+                (Number::U32(x), Number::U32(y)) => x.$checked(y).map(Number::U32),
+                (Number::U64(x), Number::U64(y)) => x.$checked(y).map(Number::U64),
+                (Number::Float(x), Number::Float(y)) => Some(Number::Float($clj(x, y))),
+                (Number::F64(x), Number::F64(y)) => Some(Number::F64($clj(x, y))),
+                (Number::F32(x), Number::F32(y)) => Some(Number::F32($clj(x, y))),
+                (n, o) => return Err(number_mismatch(n, o, &$b.span)),
+            },
+            (Literal::Num(_), value) => return Err(type_mismatch("a number", &value, &$b.span)),
+            (value, _) => return Err(type_mismatch("a number", &value, &$a.span)),
+        }
+    };
+}
+
+/// Applies `$clj` to two integers, after [unifying][unify()] their types, giving back a [Number] of that type.
+/// Returns a type mismatch from the enclosing function for floats and anything else, pointing at the operand expressions `$a` or `$b`
+macro_rules! for_every_integer_Value {
+    ($expr:expr, ($a:expr, $b:expr), $clj:expr) => {
+        match $expr {
+            (Literal::Num(n), _) if n.number_type().is_float() => {
+                return Err(type_mismatch("an integer", &Literal::Num(n), &$a.span))
+            }
+            (_, Literal::Num(o)) if o.number_type().is_float() => {
+                return Err(type_mismatch("an integer", &Literal::Num(o), &$b.span))
+            }
+            (Literal::Num(n), Literal::Num(o)) => match unify(n, o, &$a.span, &$b.span)? {
                 (Number::Integer(x), Number::Integer(y)) => Number::Integer($clj(x, y)),
-                // (Number::UInteger(x), Number::UInteger(y)) => Number::UInteger($clj(x, y)),
-                (Number::Float(x), Number::Float(y)) => Number::Float($clj(x, y)),
-                _ => unimplemented!(),
+                (Number::Isize(x), Number::Isize(y)) => Number::Isize($clj(x, y)),
+                (Number::UInteger(x), Number::UInteger(y)) => Number::UInteger($clj(x, y)),
+                (Number::I8(x), Number::I8(y)) => Number::I8($clj(x, y)),
+                (Number::I16(x), Number::I16(y)) => Number::I16($clj(x, y)),
+                (Number::I32(x), Number::I32(y)) => Number::I32($clj(x, y)),
+                (Number::I64(x), Number::I64(y)) => Number::I64($clj(x, y)),
+                (Number::U8(x), Number::U8(y)) => Number::U8($clj(x, y)),
+                (Number::U16(x), Number::U16(y)) => Number::U16($clj(x, y)),
+                (Number::U32(x), Number::U32(y)) => Number::U32($clj(x, y)),
+                (Number::U64(x), Number::U64(y)) => Number::U64($clj(x, y)),
+                (n, o) => return Err(number_mismatch(n, o, &$b.span)),
             },
-            _ => unimplemented!(),
+            (Literal::Num(_), value) => return Err(type_mismatch("an integer", &value, &$b.span)),
+            (value, _) => return Err(type_mismatch("an integer", &value, &$a.span)),
+        }
+    };
+}
+
+/// Maximum depth of nested function calls. Deeper recursions are stopped before they overflow the host's stack
+pub const RECURSION_LIMIT: usize = 1000;
+
+/// Maximum depth of nested blocks and expressions being evaluated, counting the ones of every function in a recursion.
+/// Limiting calls alone isn't enough, as each of them can nest deeply on its own
+pub const NESTING_LIMIT: usize = 10_000;
+
+/// Maximum depth of nested brackets and prefix operators in source, as in `((1))` or `--1`.
+/// Parsing is recursive too, so deeper programs are rejected before they are parsed
+pub const PARSE_NESTING_LIMIT: usize = 1000;
+
+/// Size of the stack [eval_source()] runs on. Evaluation is recursive, and each nested block or expression takes quite a bit of it
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// Reasons for an evaluation to stop before reaching the end of a block.
+/// Loop control and `return` travel on the `Err` side, so `?` carries them up to the construct that handles them
+#[derive(Debug)]
+enum Unwind {
+    /// `break 'label value`. Handled by the labeled loop, or by the innermost one if no label is given
+    Break(Option<String>, Option<Spanned<Literal>>, Span),
+    /// `continue 'label`. Handled by the labeled loop, or by the innermost one if no label is given
+    Continue(Option<String>, Span),
+    /// `return value`. Handled by the function call
+    Return(Literal),
+    /// An actual evaluation error
+    Error(Error),
+}
+
+impl From<Error> for Unwind {
+    fn from(err: Error) -> Self {
+        Self::Error(err)
+    }
+}
+
+/// Error for a value of an unexpected type, coming from the expression at `span`
+fn type_mismatch(expected: &str, found: &Literal, span: &Span) -> Unwind {
+    Unwind::Error(Error::TypeMismatch {
+        expected: expected.to_string(),
+        found: found.type_name().to_string(),
+        span: span.clone(),
+    })
+}
+
+/// Error for an operand of a different number type than the other, coming from the expression at `span`
+fn number_mismatch(expected: Number, found: Number, span: &Span) -> Unwind {
+    type_mismatch(
+        &format!("`{}`", expected.number_type().name()),
+        &Literal::Num(found),
+        span,
+    )
+}
+
+/// Brings two numbers to the same type, so they can be operated on. Numbers without a suffix adapt to the other one:
+/// integers to any number, if their value fits in it, and floats to any float.
+/// Numbers of two different types that were written as such don't mix, as in Rust
+fn unify(n: Number, o: Number, n_span: &Span, o_span: &Span) -> Result<(Number, Number), Unwind> {
+    match (n.number_type(), o.number_type()) {
+        (n_type, o_type) if n_type == o_type => Ok((n, o)),
+        (NumberType::Integer | NumberType::Float, o_type) if o_type.is_float() => {
+            Ok((n.cast(o_type), o))
+        }
+        (n_type, NumberType::Integer | NumberType::Float) if n_type.is_float() => {
+            Ok((n, o.cast(n_type)))
+        }
+        (NumberType::Integer, o_type) => match n.try_cast(o_type) {
+            Some(n) => Ok((n, o)),
+            None => Err(overflow("convert", n_span)),
+        },
+        (n_type, NumberType::Integer) => match o.try_cast(n_type) {
+            Some(o) => Ok((n, o)),
+            None => Err(overflow("convert", o_span)),
+        },
+        _ => Err(number_mismatch(n, o, o_span)),
+    }
+}
+
+/// Fails with a division by zero if `right` is an integer zero once [unified][unify()] with `left`.
+/// A zero that becomes a float is fine, as in `5.0 / 0`, which is infinity
+fn check_divisor(
+    left: &Literal,
+    right: &Literal,
+    (a, b): (&Spanned<Expr>, &Spanned<Expr>),
+    span: &Span,
+) -> Result<(), Unwind> {
+    if let (Literal::Num(n), Literal::Num(o)) = (left, right) {
+        if unify(*n, *o, &a.span, &b.span)?.1.is_integer_zero() {
+            return Err(Error::DivisionByZero { span: span.clone() }.into());
+        }
+    }
+    Ok(())
+}
+
+/// Orders two values the way Rust does: numbers after [unifying][unify()] them, `bool`s, `str`s and `char`s as usual,
+/// and arrays element by element. `None` means the values are unordered, as happens with NaNs.
+/// Values of different types, functions and ranges can't be compared, and are reported at the operand expressions `a` or `b`
+fn compare(
+    left: Literal,
+    right: Literal,
+    a: &Spanned<Expr>,
+    b: &Spanned<Expr>,
+) -> Result<Option<Ordering>, Unwind> {
+    match (left, right) {
+        (Literal::Null, Literal::Null) => Ok(Some(Ordering::Equal)),
+        (Literal::Bool(x), Literal::Bool(y)) => Ok(Some(x.cmp(&y))),
+        (Literal::Str(x), Literal::Str(y)) => Ok(Some(x.cmp(&y))),
+        (Literal::Char(x), Literal::Char(y)) => Ok(Some(x.cmp(&y))),
+        (Literal::Array(xs), Literal::Array(ys)) => {
+            let len_ordering = xs.len().cmp(&ys.len());
+            for (x, y) in xs.into_iter().zip(ys) {
+                match compare(x, y, a, b)? {
+                    Some(Ordering::Equal) => continue,
+                    ordering => return Ok(ordering),
+                }
+            }
+            Ok(Some(len_ordering))
+        }
+        (left @ Literal::Num(_), right) => {
+            Ok(for_every_number_Value!((left, right), (a, b), |x, y| {
+                PartialOrd::partial_cmp(&x, &y)
+            }))
+        }
+        (left @ (Literal::Fn(_) | Literal::Range { .. }), _) => {
+            Err(type_mismatch("a comparable value", &left, &a.span))
+        }
+        (_, right @ (Literal::Fn(_) | Literal::Range { .. })) => {
+            Err(type_mismatch("a comparable value", &right, &b.span))
+        }
+        (left, right) => Err(type_mismatch(
+            &format!("`{}`", left.type_name()),
+            &right,
+            &b.span,
+        )),
+    }
+}
+
+/// Turns the ends of a range into `isize`s, which is what ranges are made of
+fn range_bound(bound: Literal, span: &Span) -> Result<isize, Unwind> {
+    match bound {
+        Literal::Num(n) if !n.number_type().is_float() => match n.try_cast(NumberType::Integer) {
+            Some(Number::Integer(x)) => Ok(x),
+            _ => Err(overflow("convert", span)),
+        },
+        value => Err(type_mismatch("an integer", &value, span)),
+    }
+}
+
+/// Turns the right operand of `**`, `<<` and `>>` into a `u32`, which is what Rust takes for them.
+/// `operand` says what it is in the error for integers that don't fit, as happens with negative ones
+fn u32_operand(value: Literal, operand: &'static str, span: &Span) -> Result<u32, Unwind> {
+    match value {
+        Literal::Num(n) if !n.number_type().is_float() => match n.try_cast(NumberType::U32) {
+            Some(Number::U32(x)) => Ok(x),
+            _ => Err(Error::OperandOutOfRange {
+                operand,
+                value: n.to_string(),
+                span: span.clone(),
+            }
+            .into()),
+        },
+        value => Err(type_mismatch("an integer", &value, span)),
+    }
+}
+
+/// Turns the value of an index expression into a `usize`, which is what arrays are indexed with
+fn index_value(index: Literal, span: &Span) -> Result<usize, Unwind> {
+    match index {
+        Literal::Num(n) if !n.number_type().is_float() => match n.try_cast(NumberType::UInteger) {
+            Some(Number::UInteger(x)) => Ok(x),
+            // Every integer that doesn't fit is negative
+            _ => Err(Error::NegativeIndex { span: span.clone() }.into()),
+        },
+        value => Err(type_mismatch("an integer", &value, span)),
+    }
+}
+
+/// Indexes `value` with `index`, which is either a position or a range to slice it with.
+/// `value_span`, `index_span` and `span` are the spans of the value, the index and the whole indexing
+fn index(
+    value: Literal,
+    index: Literal,
+    value_span: &Span,
+    index_span: &Span,
+    span: &Span,
+) -> Result<Literal, Unwind> {
+    let index = match index {
+        Literal::Range {
+            start,
+            end,
+            inclusive,
+        } => return slice(value, (start, end, inclusive), value_span, index_span, span),
+        index => index_value(index, index_span)?,
+    };
+    let out_of_bounds = |len| Error::IndexOutOfBounds {
+        index,
+        len,
+        span: span.clone(),
+    };
+    match value {
+        Literal::Array(array) => {
+            let len = array.len();
+            array
+                .into_iter()
+                .nth(index)
+                .ok_or_else(|| out_of_bounds(len).into())
+        }
+        // Strings are indexed by `char`, not by byte, and give back that `char`
+        Literal::Str(string) => string
+            .chars()
+            .nth(index)
+            .map(Literal::Char)
+            .ok_or_else(|| out_of_bounds(string.chars().count()).into()),
+        value => Err(type_mismatch("an array or `str`", &value, value_span)),
+    }
+}
+
+/// Slices `value` from `start` up to `end`, which is only included if the range is `inclusive`. Arrays are sliced by element, and `str`s by `char`.
+/// `value_span`, `range_span` and `span` are the spans of the value, the range and the whole slicing
+fn slice(
+    value: Literal,
+    (start, end, inclusive): (isize, isize, bool),
+    value_span: &Span,
+    range_span: &Span,
+    span: &Span,
+) -> Result<Literal, Unwind> {
+    let (start, end) = match (usize::try_from(start), usize::try_from(end)) {
+        // Can't overflow, as `end` came from an `isize`
+        (Ok(start), Ok(end)) => (start, end + inclusive as usize),
+        _ => {
+            return Err(Error::NegativeIndex {
+                span: range_span.clone(),
+            }
+            .into())
         }
     };
+    let in_bounds = |len| match start <= end && end <= len {
+        true => Ok(()),
+        false => Err(Error::SliceOutOfBounds {
+            start,
+            end,
+            len,
+            span: span.clone(),
+        }),
+    };
+    match value {
+        Literal::Array(array) => {
+            in_bounds(array.len())?;
+            Ok(Literal::Array(array[start..end].to_vec()))
+        }
+        Literal::Str(string) => {
+            in_bounds(string.chars().count())?;
+            Ok(Literal::Str(
+                string.chars().skip(start).take(end - start).collect(),
+            ))
+        }
+        value => Err(type_mismatch("an array or `str`", &value, value_span)),
+    }
+}
+
+/// A [Place] with its indices already evaluated: the variable it starts from, and the indices taken from it in order.
+/// Each index comes with the spans of the array it is taken from and of the whole indexing
+type EvaluatedPlace = (Spanned<String>, Vec<(usize, Span, Span)>);
+
+/// Evaluates the indices of `place`, from the outermost array inwards
+fn eval_place(
+    place: &Spanned<Place>,
+    vars: &mut Scopes,
+    funcs: &HashMap<String, &Function>,
+) -> Result<EvaluatedPlace, Unwind> {
+    match &place.node {
+        Place::Var(name) => Ok((Spanned::new(name.clone(), place.span.clone()), Vec::new())),
+        Place::Index(array, index) => {
+            let (name, mut indices) = eval_place(array, vars, funcs)?;
+            let value = index_value(eval_expr(index, vars, funcs)?, &index.span)?;
+            indices.push((value, array.span.clone(), place.span.clone()));
+            Ok((name, indices))
+        }
+    }
+}
+
+/// The value stored at an evaluated place, for it to be read or written.
+/// Fails if the variable doesn't exist, if something indexed is not an array, or if an index is out of its bounds
+fn place_mut<'a>(
+    vars: &'a mut Scopes,
+    (name, indices): &EvaluatedPlace,
+) -> Result<&'a mut Literal, Unwind> {
+    let mut value = vars
+        .get_mut(&name.node)
+        .ok_or_else(|| Error::UndefinedVariable {
+            name: name.node.clone(),
+            span: name.span.clone(),
+        })?;
+    for (index, array_span, span) in indices {
+        value = match value {
+            Literal::Array(array) => {
+                let len = array.len();
+                array
+                    .get_mut(*index)
+                    .ok_or_else(|| Error::IndexOutOfBounds {
+                        index: *index,
+                        len,
+                        span: span.clone(),
+                    })?
+            }
+            value => return Err(type_mismatch("an array", value, array_span)),
+        };
+    }
+    Ok(value)
+}
+
+/// Error for an integer `operation` that overflowed at `span`
+fn overflow(operation: &'static str, span: &Span) -> Unwind {
+    Unwind::Error(Error::Overflow {
+        operation,
+        span: span.clone(),
+    })
+}
+
+/// Variables reachable during evaluation, in nested lexical scopes.
+/// Every block has a scope of its own, and every function call starts a frame that can't see the variables of its callers
+#[derive(Debug, Default)]
+struct Scopes {
+    /// Innermost scope last. Every name holds its bindings in the scope, with the ones shadowing the others last
+    scopes: Vec<HashMap<String, Vec<Literal>>>,
+    /// Index of the outermost scope of the function being evaluated
+    frame: usize,
+    /// Number of nested function calls
+    calls: usize,
+    /// Number of nested blocks and expressions being evaluated, across function calls
+    depth: usize,
+    /// Function items of the innermost block that has any, linked to the ones of enclosing blocks
+    items: Option<Arc<Items>>,
+    /// What the function being evaluated captured. Its variables are shared by every call, and come after the frame's own
+    captures: Arc<Captures>,
+}
+
+impl Scopes {
+    /// Current binding of `name`, from the nearest scope in the current frame that has it, or from the captures
+    fn get(&self, name: &str) -> Option<&Literal> {
+        self.scopes[self.frame..]
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).and_then(|bindings| bindings.last()))
+            .or_else(|| self.captures.vars.get(name))
+    }
+
+    /// Same as [get()][Scopes::get()], for the binding to be changed. Captures can't be changed
+    fn get_mut(&mut self, name: &str) -> Option<&mut Literal> {
+        self.scopes[self.frame..]
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name).and_then(|bindings| bindings.last_mut()))
+    }
+
+    /// Declares `name` in the innermost scope, shadowing any other binding with that name
+    fn declare(&mut self, name: String, value: Literal) {
+        match self.scopes.last_mut() {
+            Some(scope) => scope.entry(name).or_default().push(value),
+            None => self.scopes.push(HashMap::from([(name, vec![value])])),
+        }
+    }
+
+    /// Evaluates `f` inside a new innermost `scope`, which is dropped afterwards
+    fn scoped<T>(
+        &mut self,
+        scope: HashMap<String, Vec<Literal>>,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        let len = self.scopes.len();
+        self.scopes.push(scope);
+        let output = f(self);
+        self.scopes.truncate(len);
+        output
+    }
+
+    /// Evaluates `f` one level deeper into the program, or fails at `span` if that goes over [NESTING_LIMIT]
+    fn nested<T>(
+        &mut self,
+        span: &Span,
+        f: impl FnOnce(&mut Self) -> Result<T, Unwind>,
+    ) -> Result<T, Unwind> {
+        if self.depth >= NESTING_LIMIT {
+            return Err(Error::NestingLimit {
+                limit: NESTING_LIMIT,
+                span: span.clone(),
+            }
+            .into());
+        }
+        self.depth += 1;
+        let output = f(self);
+        self.depth -= 1;
+        output
+    }
+
+    /// Evaluates `f` in a new frame for a function call, starting with the `args` scope and the function's `captures`.
+    /// `items` are the ones visible where the function is declared, which may not be visible where it is called
+    fn call<T>(
+        &mut self,
+        args: HashMap<String, Vec<Literal>>,
+        captures: Arc<Captures>,
+        items: Option<Arc<Items>>,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        let frame = std::mem::replace(&mut self.frame, self.scopes.len());
+        let captures = std::mem::replace(&mut self.captures, captures);
+        let items = std::mem::replace(&mut self.items, items);
+        self.calls += 1;
+        let output = self.scoped(args, f);
+        self.calls -= 1;
+        self.items = items;
+        self.captures = captures;
+        self.frame = frame;
+        output
+    }
+
+    /// Evaluates `f` with the function items of a block in scope, which can be called from anywhere inside it,
+    /// even before they are declared, and from each other
+    fn with_items<T>(&mut self, block: &Block, f: impl FnOnce(&mut Self) -> T) -> T {
+        let functions: HashMap<_, _> = block
+            .0
+            .iter()
+            .filter_map(|statement| match &statement.node {
+                Statement::Item(item) => match item.as_ref() {
+                    Item::Function(function) => {
+                        Some((function.name.clone(), Arc::new(function.clone())))
+                    }
+                },
+                _ => None,
+            })
+            .collect();
+        if functions.is_empty() {
+            return f(self);
+        }
+        let parent = self.items.take();
+        self.items = Some(Arc::new(Items {
+            functions,
+            parent: parent.clone(),
+        }));
+        let output = f(self);
+        self.items = parent;
+        output
+    }
+
+    /// Function item called `name` in the nearest block that declares one, along with the items visible from its body.
+    /// `None` if no block does, in which case it may still be a top-level function
+    fn function(&self, name: &str) -> Option<(Arc<Function>, Arc<Items>)> {
+        let mut items = self.items.as_ref();
+        while let Some(block_items) = items {
+            if let Some(function) = block_items.functions.get(name) {
+                return Some((function.clone(), block_items.clone()));
+            }
+            items = block_items.parent.as_ref();
+        }
+        None
+    }
 }
 
 /// Evaluates return value
-fn eval_expr<'a>(
-    expr: &'a Expr,
-    vars: &mut Vec<HashMap<String, Vec<Literal>>>,
+fn eval_expr(
+    expr: &Spanned<Expr>,
+    vars: &mut Scopes,
+    funcs: &HashMap<String, &Function>,
+) -> Result<Literal, Unwind> {
+    vars.nested(&expr.span, |vars| eval_expr_inner(expr, vars, funcs))
+}
+
+/// Same as [eval_expr()], without counting `expr` towards [NESTING_LIMIT]
+fn eval_expr_inner(
+    expr: &Spanned<Expr>,
+    vars: &mut Scopes,
     funcs: &HashMap<String, &Function>,
-) -> Result<Literal, String> {
-    match expr {
+) -> Result<Literal, Unwind> {
+    match &expr.node {
         Expr::Literal(x) => Ok(x.clone()),
-        Expr::Lt(a, b) => Ok(Literal::Bool({
-            let left = eval_expr(a, vars, funcs)?;
-            let right = eval_expr(b, vars, funcs)?;
-            for_every_number_Value!((left, right), |x, y| x < y)
-        })),
-        Expr::Gt(a, b) => Ok(Literal::Bool({
-            let left = eval_expr(a, vars, funcs)?;
-            let right = eval_expr(b, vars, funcs)?;
-            for_every_number_Value!((left, right), |x, y| x > y)
-        })),
-        Expr::Eq(a, b) => Ok(Literal::Bool({
-            let left = eval_expr(a, vars, funcs)?;
-            let right = eval_expr(b, vars, funcs)?;
-            for_every_number_Value!((left, right), |x, y| x == y)
-        })),
-        Expr::Neg(a) => match eval_expr(a, vars, funcs)? {
-            Literal::Num(x) => Ok(Literal::Num(-x)),
-            Literal::Bool(x) => Ok(Literal::Bool(!x)),
-            _ => Err("Cannot apply negation".to_string()),
-        },
-        Expr::And(a, b) => match (eval_expr(a, vars, funcs)?, eval_expr(b, vars, funcs)?) {
-            (Literal::Num(x), Literal::Num(y)) => Ok(Literal::Bool(
-                x > Number::Integer(1) && y > Number::Integer(1),
-            )),
-            (Literal::Bool(x), Literal::Bool(y)) => Ok(Literal::Bool(x && y)),
-            _ => Err("Cannot apply AND".to_string()),
+        // The right operand is only evaluated when the left one doesn't settle the result, as in Rust
+        Expr::And(a, b) => match eval_expr(a, vars, funcs)? {
+            Literal::Bool(false) => Ok(Literal::Bool(false)),
+            Literal::Bool(true) => match eval_expr(b, vars, funcs)? {
+                Literal::Bool(y) => Ok(Literal::Bool(y)),
+                value => Err(type_mismatch("`bool`", &value, &b.span)),
+            },
+            value => Err(type_mismatch("`bool`", &value, &a.span)),
         },
-        Expr::Or(a, b) => match (eval_expr(a, vars, funcs)?, eval_expr(b, vars, funcs)?) {
-            (Literal::Num(x), Literal::Num(y)) => Ok(Literal::Bool(
-                x > Number::Integer(1) || y > Number::Integer(1),
-            )),
-            (Literal::Bool(x), Literal::Bool(y)) => Ok(Literal::Bool(x || y)),
-            _ => Err("Cannot apply OR".to_string()),
+        Expr::Or(a, b) => match eval_expr(a, vars, funcs)? {
+            Literal::Bool(true) => Ok(Literal::Bool(true)),
+            Literal::Bool(false) => match eval_expr(b, vars, funcs)? {
+                Literal::Bool(y) => Ok(Literal::Bool(y)),
+                value => Err(type_mismatch("`bool`", &value, &b.span)),
+            },
+            value => Err(type_mismatch("`bool`", &value, &a.span)),
         },
-        Expr::Add(a, b) => Ok(Literal::Num({
-            let left = eval_expr(a, vars, funcs)?;
-            let right = eval_expr(b, vars, funcs)?;
-            for_every_number_Value_wrapped!((left, right), |x, y| x + y)
-        })),
-        Expr::Sub(a, b) => Ok(Literal::Num({
+        // The operation itself is done by operate(), so its bulk stays off the stack of nested evaluations
+        Expr::Lt(a, b)
+        | Expr::Gt(a, b)
+        | Expr::Le(a, b)
+        | Expr::Ge(a, b)
+        | Expr::Eq(a, b)
+        | Expr::Ne(a, b)
+        | Expr::Add(a, b)
+        | Expr::Sub(a, b)
+        | Expr::Mul(a, b)
+        | Expr::Div(a, b)
+        | Expr::Rem(a, b)
+        | Expr::Pow(a, b)
+        | Expr::BitAnd(a, b)
+        | Expr::BitOr(a, b)
+        | Expr::BitXor(a, b)
+        | Expr::Shl(a, b)
+        | Expr::Shr(a, b) => {
             let left = eval_expr(a, vars, funcs)?;
             let right = eval_expr(b, vars, funcs)?;
-            for_every_number_Value_wrapped!((left, right), |x, y| x - y)
-        })),
-        Expr::Mul(a, b) => Ok(Literal::Num({
-            let left = eval_expr(a, vars, funcs)?;
-            let right = eval_expr(b, vars, funcs)?;
-            for_every_number_Value_wrapped!((left, right), |x, y| x * y)
-        })),
-        Expr::Div(a, b) => Ok(Literal::Num({
-            let left = eval_expr(a, vars, funcs)?;
-            let right = eval_expr(b, vars, funcs)?;
-            for_every_number_Value_wrapped!((left, right), |x, y| x / y)
+            operate(expr, left, right)
+        }
+        Expr::Var(name) => variable(name, &expr.span, vars, funcs),
+        Expr::Closure {
+            args,
+            body,
+            captures,
+        } => Ok(Literal::Fn(Function {
+            name: CLOSURE_NAME.to_string(),
+            args: args.clone(),
+            body: body.clone(),
+            captures: Arc::new(Captures {
+                // Names that aren't variables here are left for the body to report, or are functions
+                vars: captures
+                    .iter()
+                    .filter_map(|name| Some((name.clone(), vars.get(name)?.clone())))
+                    .collect(),
+                items: vars.items.clone(),
+            }),
         })),
-        Expr::Var { name, index } => {
-            // Searches the variable on variables symbol table that matches name with invoked variable
-            let mut retval = None;
-            for scope_vars in vars.iter().rev() {
-                if let Some(var_vec) = scope_vars.get(name) {
-                    if let Some(var) = var_vec.iter().last() {
-                        retval = Some(Ok(var.to_owned()));
-                        break;
-                    } else {
-                        retval = Some(Err(format!("Cannot find variable `{}` in scope", name)));
-                    }
-                } else {
-                    retval = Some(Err(format!("Cannot find variable `{}`", name)));
-                }
-            }
-
-            // If index is some, return value at index, if value is array
-            if let &Expr::Literal(Literal::Num(Number::Integer(index_number))) = index.as_ref() {
-                if index_number > 0 {
-                    if let Literal::Array(array) = retval.unwrap().unwrap() {
-                        retval = Some(Ok(array[index_number as usize].clone()))
-                    } else {
-                        retval = Some(Err("Cannot index non-array".to_string()))
-                    }
-                }
-            } else {
-                retval = Some(Err("Cannot index with valu less than zero".to_string()));
-            }
-            retval.unwrap()
+        Expr::Index(a, i) => {
+            let value = eval_expr(a, vars, funcs)?;
+            let index = eval_expr(i, vars, funcs)?;
+            self::index(value, index, &a.span, &i.span, &expr.span)
+        }
+        Expr::Range(a, b) | Expr::RangeInclusive(a, b) => Ok(Literal::Range {
+            start: range_bound(eval_expr(a, vars, funcs)?, &a.span)?,
+            end: range_bound(eval_expr(b, vars, funcs)?, &b.span)?,
+            inclusive: matches!(expr.node, Expr::RangeInclusive(..)),
+        }),
+        Expr::Neg(a) | Expr::Not(a) | Expr::Cast(a, _) => {
+            let value = eval_expr(a, vars, funcs)?;
+            operate_unary(expr, value)
         }
+        Expr::Loop(r#loop) => eval_loop(r#loop, vars, funcs),
         Expr::Array(array) => {
             let mut retval = Vec::new();
             for expr in array {
@@ -144,147 +667,588 @@ fn eval_expr<'a>(
             }
             Ok(Literal::Array(retval))
         }
-        Expr::Call(name, call_args) => {
-            // Retrieve the callee signature
-            if let Some(function) = funcs.get(name) {
-                if function.args.len() == call_args.len() {
-                    // Combine passed args to argument name as `(name, Value)`, similar to variables
-                    let args = function.args.iter().zip(
-                        call_args
-                            .iter()
-                            .map(|arg| eval_expr(arg, vars, funcs).unwrap()),
-                    );
-
-                    let mut new_scope = HashMap::new();
-
-                    // Include passed variables to the scope inside the function
-                    for arg in args {
-                        new_scope.insert(arg.0.to_owned(), vec![arg.1]);
-                    }
+        Expr::Macro {
+            r#macro,
+            format,
+            args,
+        } => {
+            let mut values = Vec::new();
+            for arg in args {
+                values.push(Spanned::new(eval_expr(arg, vars, funcs)?, arg.span.clone()));
+            }
+            output(r#macro, format, &values)
+        }
+        Expr::MethodCall {
+            receiver,
+            method,
+            args,
+        } => {
+            let receiver = eval_expr(receiver, vars, funcs)?;
+            let mut values = Vec::new();
+            for arg in args {
+                values.push(Spanned::new(eval_expr(arg, vars, funcs)?, arg.span.clone()));
+            }
+            methods::call_method(receiver, method, values, &expr.span)
+        }
+        Expr::Call(callee, call_args) => eval_call(callee, call_args, &expr.span, vars, funcs),
+    }
+}
 
-                    vars.push(new_scope);
+/// Value of the variable `name`, or of the function with that name if there's no such variable
+fn variable(
+    name: &str,
+    span: &Span,
+    vars: &Scopes,
+    funcs: &HashMap<String, &Function>,
+) -> Result<Literal, Unwind> {
+    // Searches the variable on variables symbol table that matches name with invoked variable
+    if let Some(value) = vars.get(name) {
+        return Ok(value.clone());
+    }
+    // Functions can be used as values too, taking the items they can call along
+    let function = match vars.function(name) {
+        Some((function, items)) => Function {
+            captures: Arc::new(Captures {
+                vars: HashMap::new(),
+                items: Some(items),
+            }),
+            ..function.as_ref().clone()
+        },
+        None => funcs
+            .get(name)
+            .map(|&function| function.clone())
+            .ok_or_else(|| Error::UndefinedVariable {
+                name: name.to_string(),
+                span: span.clone(),
+            })?,
+    };
+    Ok(Literal::Fn(function))
+}
 
-                    // Evaluated the function result
-                    let output = eval(&function.body, vars, funcs, false);
-                    // Remove passed vars from scope, as the scope is outside the function now
+/// Applies the unary operator or cast of `expr` to its already evaluated operand `value`
+fn operate_unary(expr: &Spanned<Expr>, value: Literal) -> Result<Literal, Unwind> {
+    match &expr.node {
+        Expr::Neg(a) => match value {
+            Literal::Num(x) if !x.number_type().is_unsigned() => x
+                .checked_neg()
+                .map(Literal::Num)
+                .ok_or_else(|| overflow("negate", &expr.span)),
+            Literal::Bool(x) => Ok(Literal::Bool(!x)),
+            value => Err(type_mismatch("a signed number or `bool`", &value, &a.span)),
+        },
+        Expr::Not(a) => match value {
+            Literal::Bool(x) => Ok(Literal::Bool(!x)),
+            // Floats have no bits to flip
+            Literal::Num(x) => x
+                .bitwise_not()
+                .map(Literal::Num)
+                .ok_or_else(|| type_mismatch("an integer or `bool`", &Literal::Num(x), &a.span)),
+            value => Err(type_mismatch("an integer or `bool`", &value, &a.span)),
+        },
+        Expr::Cast(a, to) => match value {
+            Literal::Num(x) => Ok(Literal::Num(x.cast(*to))),
+            Literal::Bool(x) if !to.is_float() => Ok(Literal::Num(Number::U8(x as u8).cast(*to))),
+            Literal::Char(x) if !to.is_float() => Ok(Literal::Num(Number::U32(x as u32).cast(*to))),
+            value => Err(type_mismatch("a number, `bool` or `char`", &value, &a.span)),
+        },
+        _ => unreachable!("not a unary operator or cast"),
+    }
+}
 
-                    vars.pop();
+/// Formats `values` as `format` says, and either gives the text back or writes it out, depending on `r#macro`
+fn output(
+    r#macro: &Macro,
+    format: &[FormatPiece],
+    values: &[Spanned<Literal>],
+) -> Result<Literal, Unwind> {
+    let text = format::format(format, values)?;
+    // Failing to write output is not an error of the program, so it is ignored instead of panicking as `print!` would
+    let _ = match r#macro {
+        Macro::Format => return Ok(Literal::Str(text)),
+        Macro::Print => write!(io::stdout(), "{}", text),
+        Macro::Println => writeln!(io::stdout(), "{}", text),
+        Macro::Eprint => write!(io::stderr(), "{}", text),
+        Macro::Eprintln => writeln!(io::stderr(), "{}", text),
+    };
+    Ok(Literal::Null)
+}
 
-                    output
-                } else {
-                    Err(format!(
-                        "Wrong number of arguments for function `{}`: expected {}, found {}",
-                        name,
-                        function.args.len(),
-                        call_args.len(),
-                    ))
+/// Applies the binary operator of `expr` to the already evaluated `left` and `right` operands
+fn operate(expr: &Spanned<Expr>, left: Literal, right: Literal) -> Result<Literal, Unwind> {
+    match &expr.node {
+        Expr::Lt(a, b) => {
+            let ordering = compare(left, right, a, b)?;
+            Ok(Literal::Bool(ordering == Some(Ordering::Less)))
+        }
+        Expr::Gt(a, b) => {
+            let ordering = compare(left, right, a, b)?;
+            Ok(Literal::Bool(ordering == Some(Ordering::Greater)))
+        }
+        Expr::Le(a, b) => {
+            let ordering = compare(left, right, a, b)?;
+            Ok(Literal::Bool(matches!(
+                ordering,
+                Some(Ordering::Less | Ordering::Equal)
+            )))
+        }
+        Expr::Ge(a, b) => {
+            let ordering = compare(left, right, a, b)?;
+            Ok(Literal::Bool(matches!(
+                ordering,
+                Some(Ordering::Greater | Ordering::Equal)
+            )))
+        }
+        Expr::Eq(a, b) => {
+            let ordering = compare(left, right, a, b)?;
+            Ok(Literal::Bool(ordering == Some(Ordering::Equal)))
+        }
+        Expr::Ne(a, b) => {
+            let ordering = compare(left, right, a, b)?;
+            Ok(Literal::Bool(ordering != Some(Ordering::Equal)))
+        }
+        Expr::Add(a, b) => match (left, right) {
+            (Literal::Str(left), Literal::Str(right)) => Ok(Literal::Str(left + &right)),
+            (Literal::Str(_), right) => Err(type_mismatch("`str`", &right, &b.span)),
+            (left, right) => {
+                for_every_number_Value_wrapped!((left, right), (a, b), checked_add, |x, y| x + y)
+                    .map(Literal::Num)
+                    .ok_or_else(|| overflow("add", &expr.span))
+            }
+        },
+        Expr::Sub(a, b) => {
+            for_every_number_Value_wrapped!((left, right), (a, b), checked_sub, |x, y| x - y)
+                .map(Literal::Num)
+                .ok_or_else(|| overflow("subtract", &expr.span))
+        }
+        Expr::Mul(a, b) => {
+            for_every_number_Value_wrapped!((left, right), (a, b), checked_mul, |x, y| x * y)
+                .map(Literal::Num)
+                .ok_or_else(|| overflow("multiply", &expr.span))
+        }
+        Expr::Div(a, b) => {
+            check_divisor(&left, &right, (a, b), &expr.span)?;
+            for_every_number_Value_wrapped!((left, right), (a, b), checked_div, |x, y| x / y)
+                .map(Literal::Num)
+                .ok_or_else(|| overflow("divide", &expr.span))
+        }
+        Expr::Rem(a, b) => {
+            check_divisor(&left, &right, (a, b), &expr.span)?;
+            for_every_number_Value_wrapped!((left, right), (a, b), checked_rem, |x, y| x % y)
+                .map(Literal::Num)
+                .ok_or_else(|| overflow("calculate the remainder", &expr.span))
+        }
+        Expr::Pow(a, b) => match (left, right) {
+            // Floats take any exponent, and a float on either side makes it a float power
+            (Literal::Num(x), Literal::Num(y))
+                if x.number_type().is_float() || y.number_type().is_float() =>
+            {
+                match unify(x, y, &a.span, &b.span)? {
+                    (Number::Float(x), Number::Float(y)) => {
+                        Ok(Literal::Num(Number::Float(x.powf(y))))
+                    }
+                    (Number::F64(x), Number::F64(y)) => Ok(Literal::Num(Number::F64(x.powf(y)))),
+                    (Number::F32(x), Number::F32(y)) => Ok(Literal::Num(Number::F32(x.powf(y)))),
+                    (x, y) => Err(number_mismatch(x, y, &b.span)),
                 }
+            }
+            // Integers only take exponents that fit a `u32`, as in Rust's `pow()`
+            (Literal::Num(x), right) => {
+                let exp = u32_operand(right, "exponent", &b.span)?;
+                x.checked_pow(exp)
+                    .map(Literal::Num)
+                    .ok_or_else(|| overflow("raise to a power", &expr.span))
+            }
+            (value, _) => Err(type_mismatch("a number", &value, &a.span)),
+        },
+        Expr::BitAnd(a, b) => match (left, right) {
+            (Literal::Bool(x), Literal::Bool(y)) => Ok(Literal::Bool(x & y)),
+            (Literal::Bool(_), value) => Err(type_mismatch("`bool`", &value, &b.span)),
+            operands => Ok(Literal::Num(for_every_integer_Value!(
+                operands,
+                (a, b),
+                |x, y| x & y
+            ))),
+        },
+        Expr::BitOr(a, b) => match (left, right) {
+            (Literal::Bool(x), Literal::Bool(y)) => Ok(Literal::Bool(x | y)),
+            (Literal::Bool(_), value) => Err(type_mismatch("`bool`", &value, &b.span)),
+            operands => Ok(Literal::Num(for_every_integer_Value!(
+                operands,
+                (a, b),
+                |x, y| x | y
+            ))),
+        },
+        Expr::BitXor(a, b) => match (left, right) {
+            (Literal::Bool(x), Literal::Bool(y)) => Ok(Literal::Bool(x ^ y)),
+            (Literal::Bool(_), value) => Err(type_mismatch("`bool`", &value, &b.span)),
+            operands => Ok(Literal::Num(for_every_integer_Value!(
+                operands,
+                (a, b),
+                |x, y| x ^ y
+            ))),
+        },
+        // The result has the type of the left operand, whatever the type of the right one is, as in Rust
+        Expr::Shl(a, b) => match left {
+            Literal::Num(x) if !x.number_type().is_float() => x
+                .checked_shl(u32_operand(right, "shift amount", &b.span)?)
+                .map(Literal::Num)
+                .ok_or_else(|| overflow("shift left", &expr.span)),
+            value => Err(type_mismatch("an integer", &value, &a.span)),
+        },
+        Expr::Shr(a, b) => match left {
+            Literal::Num(x) if !x.number_type().is_float() => x
+                .checked_shr(u32_operand(right, "shift amount", &b.span)?)
+                .map(Literal::Num)
+                .ok_or_else(|| overflow("shift right", &expr.span)),
+            value => Err(type_mismatch("an integer", &value, &a.span)),
+        },
+        _ => unreachable!(
+            "`&&` and `||` are evaluated lazily, and everything else isn't a binary operator"
+        ),
+    }
+}
+
+/// Calls `callee` with `call_args`, from the call expression at `span`
+fn eval_call(
+    callee: &Spanned<Expr>,
+    call_args: &[Spanned<Expr>],
+    span: &Span,
+    vars: &mut Scopes,
+    funcs: &HashMap<String, &Function>,
+) -> Result<Literal, Unwind> {
+    match &callee.node {
+        // Variables shadow functions items, which shadow top-level functions
+        Expr::Var(name) if vars.get(name).is_none() => {
+            if let Some((function, items)) = vars.function(name) {
+                call(&function, Some(items), call_args, span, vars, funcs)
+            } else if let Some(function) = funcs.get(name) {
+                call(function, None, call_args, span, vars, funcs)
             } else {
-                Err(format!("Cannot find function `{}`.", name))
+                Err(Error::UndefinedFunction {
+                    name: name.clone(),
+                    span: span.clone(),
+                }
+                .into())
             }
         }
+        _ => match eval_expr(callee, vars, funcs)? {
+            Literal::Fn(function) => {
+                let items = function.captures.items.clone();
+                call(&function, items, call_args, span, vars, funcs)
+            }
+            value => Err(type_mismatch("a function", &value, &callee.span)),
+        },
     }
 }
 
-/// Evaluates return value for block
-fn eval<'a>(
-    blk: &'a Block,
-    vars: &mut Vec<HashMap<String, Vec<Literal>>>,
+/// Calls `function` with `call_args`, from the call expression at `span`.
+/// `items` are the function items visible where the function was declared
+fn call(
+    function: &Function,
+    items: Option<Arc<Items>>,
+    call_args: &[Spanned<Expr>],
+    span: &Span,
+    vars: &mut Scopes,
     funcs: &HashMap<String, &Function>,
-    is_loop: bool,
-) -> Result<Literal, String> {
-    let mut last_statement = None;
-    for statement in blk.0.clone() {
-        last_statement = Some(match statement {
-            Statement::Expr(expr) => Ok(eval_expr(&expr, vars, funcs)?),
-            Statement::Block(blk) => Ok(eval(&blk, vars, funcs, false)?),
-            Statement::Break => Ok(if is_loop {
-                Literal::Break // Break special value to signal last eval broke
-            } else {
-                Literal::Null // Break outside loops are no-op
-            }),
-            Statement::Loop(r#loop) => match *r#loop {
-                Loop(blk) => Ok(loop {
-                    if let Literal::Break = eval(&blk, vars, funcs, true)? {
-                        break Literal::Null;
-                    }
-                }),
-            },
-            Statement::Item(_item) => todo!(),
+) -> Result<Literal, Unwind> {
+    if function.args.len() != call_args.len() {
+        return Err(Error::ArityMismatch {
+            name: function.name.clone(),
+            expected: function.args.len(),
+            found: call_args.len(),
+            span: span.clone(),
+        }
+        .into());
+    }
+    if vars.calls >= RECURSION_LIMIT {
+        return Err(Error::RecursionLimit {
+            limit: RECURSION_LIMIT,
+            span: span.clone(),
+        }
+        .into());
+    }
+
+    // Include passed variables to the scope inside the function, as `(name, Value)` like variables
+    let mut new_scope = HashMap::new();
+    for (arg_name, arg) in function.args.iter().zip(call_args) {
+        new_scope.insert(arg_name.to_owned(), vec![eval_expr(arg, vars, funcs)?]);
+    }
+
+    // Evaluated the function result. Only the arguments and captures are in scope inside the function,
+    // and the arguments shadow captures with the same name
+    let captures = function.captures.clone();
+    let output = vars.call(new_scope, captures, items, |vars| {
+        eval(&function.body, vars, funcs)
+    });
+
+    Ok(function_output(output)?)
+}
+
+/// Collects the value of a function body, turning a `return` into the call's value.
+/// Loop control can't go through a function boundary, so it is an error if it gets here
+fn function_output(output: Result<Literal, Unwind>) -> Result<Literal, Error> {
+    match output {
+        Ok(value) | Err(Unwind::Return(value)) => Ok(value),
+        Err(Unwind::Break(Some(label), _, span)) | Err(Unwind::Continue(Some(label), span)) => {
+            Err(Error::UndeclaredLabel { label, span })
+        }
+        Err(Unwind::Break(None, _, span)) => Err(Error::BreakOutsideLoop { span }),
+        Err(Unwind::Continue(None, span)) => Err(Error::ContinueOutsideLoop { span }),
+        Err(Unwind::Error(err)) => Err(err),
+    }
+}
+
+/// Checks the outcome of one iteration of a loop labeled `label`.
+/// Returns `Ok(None)` if the loop goes on, or `Ok(Some(value))` if it was broken out of with `break value`
+fn loop_iteration(
+    label: &Option<String>,
+    iteration: Result<Literal, Unwind>,
+) -> Result<Option<Option<Spanned<Literal>>>, Unwind> {
+    match iteration {
+        Ok(_) => Ok(None),
+        Err(Unwind::Break(target, value, _)) if target.is_none() || target == *label => {
+            Ok(Some(value))
+        }
+        Err(Unwind::Continue(target, _)) if target.is_none() || target == *label => Ok(None),
+        Err(unwind) => Err(unwind),
+    }
+}
+
+/// Checks that `break` did not carry a value out of a loop that is not a [Loop]
+fn no_break_value(value: Option<Spanned<Literal>>) -> Result<Literal, Unwind> {
+    match value {
+        Some(value) => Err(Error::BreakWithValue { span: value.span }.into()),
+        None => Ok(Literal::Null),
+    }
+}
+
+/// Evaluates a [Loop], returning the value given to the `break` that stopped it
+fn eval_loop(
+    r#loop: &Loop,
+    vars: &mut Scopes,
+    funcs: &HashMap<String, &Function>,
+) -> Result<Literal, Unwind> {
+    loop {
+        if let Some(value) = loop_iteration(&r#loop.label, eval(&r#loop.body, vars, funcs))? {
+            break Ok(value.map_or(Literal::Null, |value| value.node));
+        }
+    }
+}
+
+/// Evaluates a [While], which always evaluates to `()`
+fn eval_while(
+    r#while: &While,
+    vars: &mut Scopes,
+    funcs: &HashMap<String, &Function>,
+) -> Result<Literal, Unwind> {
+    let While { label, cond, body } = r#while;
+    loop {
+        match eval_expr(cond, vars, funcs)? {
+            Literal::Bool(true) => (),
+            Literal::Bool(false) => break Ok(Literal::Null),
+            value => return Err(type_mismatch("`bool`", &value, &cond.span)),
+        }
+        if let Some(value) = loop_iteration(label, eval(body, vars, funcs))? {
+            break no_break_value(value);
+        }
+    }
+}
+
+/// Evaluates a [For], binding its variable to every element of the range or array it iterates over
+fn eval_for(
+    r#for: &For,
+    vars: &mut Scopes,
+    funcs: &HashMap<String, &Function>,
+) -> Result<Literal, Unwind> {
+    let For {
+        label,
+        var,
+        iter,
+        body,
+    } = r#for;
+    // Ranges are iterated lazily, as they can be far too long to hold in memory
+    let integer = |x| Literal::Num(Number::Integer(x));
+    let elements: Box<dyn Iterator<Item = Literal>> = match eval_expr(iter, vars, funcs)? {
+        Literal::Range {
+            start,
+            end,
+            inclusive: false,
+        } => Box::new((start..end).map(integer)),
+        Literal::Range {
+            start,
+            end,
+            inclusive: true,
+        } => Box::new((start..=end).map(integer)),
+        Literal::Array(array) => Box::new(array.into_iter()),
+        value => return Err(type_mismatch("a range or an array", &value, &iter.span)),
+    };
+    for element in elements {
+        // Each iteration gets its own scope, holding only the element
+        let scope = HashMap::from([(var.clone(), vec![element])]);
+        let iteration = vars.scoped(scope, |vars| eval(body, vars, funcs));
+        if let Some(value) = loop_iteration(label, iteration)? {
+            return no_break_value(value);
+        }
+    }
+    Ok(Literal::Null)
+}
+
+/// Assigns `rvalue` to `lvalue`, or the result of applying `op` to both for compound assignments, giving back the new value
+fn eval_assign(
+    lvalue: &Spanned<Place>,
+    op: Option<BinaryOp>,
+    rvalue: &Spanned<Expr>,
+    vars: &mut Scopes,
+    funcs: &HashMap<String, &Function>,
+) -> Result<Literal, Unwind> {
+    let place = eval_place(lvalue, vars, funcs)?;
+    let new_value = match op {
+        None => eval_expr(rvalue, vars, funcs)?,
+        // `x op= y` is evaluated as `x op y`, with the current value of `x` as the left operand.
+        // The operands of the operation are only there for their spans, which errors point at
+        Some(op) => {
+            let current = place_mut(vars, &place)?.clone();
+            let value = eval_expr(rvalue, vars, funcs)?;
+            let span = lvalue.span.start..rvalue.span.end;
+            let lhs = Spanned::new(Expr::Literal(Literal::Null), lvalue.span.clone());
+            let rhs = Spanned::new(Expr::Literal(Literal::Null), rvalue.span.clone());
+            operate(
+                &Spanned::new(op(Box::new(lhs), Box::new(rhs)), span),
+                current,
+                value,
+            )?
+        }
+    };
+    // Updates the nearest binding of the variable, so blocks and loop bodies can change outer variables
+    *place_mut(vars, &place)? = new_value.clone();
+    Ok(new_value)
+}
+
+/// Evaluates return value for block, in a scope of its own
+fn eval(
+    blk: &Spanned<Block>,
+    vars: &mut Scopes,
+    funcs: &HashMap<String, &Function>,
+) -> Result<Literal, Unwind> {
+    vars.nested(&blk.span, |vars| {
+        vars.scoped(HashMap::new(), |vars| {
+            vars.with_items(blk, |vars| eval_statements(blk, vars, funcs))
+        })
+    })
+}
+
+/// Evaluates the statements of a block, one after the other, in the current scope
+fn eval_statements(
+    blk: &Block,
+    vars: &mut Scopes,
+    funcs: &HashMap<String, &Function>,
+) -> Result<Literal, Unwind> {
+    let mut last_statement = Literal::Null;
+    for statement in &blk.0 {
+        last_statement = match &statement.node {
+            Statement::Expr(expr) => eval_expr(expr, vars, funcs)?,
+            Statement::Block(blk) => eval(blk, vars, funcs)?,
+            Statement::Break { label, value } => {
+                let value = match value {
+                    Some(expr) => Some(Spanned::new(
+                        eval_expr(expr, vars, funcs)?,
+                        expr.span.clone(),
+                    )),
+                    None => None,
+                };
+                return Err(Unwind::Break(label.clone(), value, statement.span.clone()));
+            }
+            Statement::Continue { label } => {
+                return Err(Unwind::Continue(label.clone(), statement.span.clone()))
+            }
+            Statement::Return(value) => {
+                return Err(Unwind::Return(match value {
+                    Some(expr) => eval_expr(expr, vars, funcs)?,
+                    None => Literal::Null,
+                }))
+            }
+            Statement::Loop(r#loop) => eval_loop(r#loop, vars, funcs)?,
+            Statement::While(r#while) => eval_while(r#while, vars, funcs)?,
+            Statement::For(r#for) => eval_for(r#for, vars, funcs)?,
+            // Already brought into scope when the block started
+            Statement::Item(_) => Literal::Null,
             Statement::Conditional {
                 r#if,
                 r#then,
                 r#else,
-            } => {
-                if let Literal::Bool(cond) = eval_expr(&r#if, vars, funcs)? {
-                    if cond {
-                        Ok(eval(&r#then, vars, funcs, is_loop)?)
-                    } else if let Some(r#else) = r#else {
-                        Ok(eval(&r#else, vars, funcs, is_loop)?)
-                    } else {
-                        Ok(Literal::Null)
-                    }
-                } else {
-                    Err("Conditional's condition is not a boolean expression".to_string())
-                }
-            }
-            Statement::Let { lvalue, rvalue } => {
+            } => match eval_expr(r#if, vars, funcs)? {
+                Literal::Bool(true) => eval(r#then, vars, funcs)?,
+                Literal::Bool(false) => match r#else {
+                    Some(r#else) => eval(r#else, vars, funcs)?,
+                    None => Literal::Null,
+                },
+                value => return Err(type_mismatch("`bool`", &value, &r#if.span)),
+            },
+            Statement::Let { lvalue, rvalue, .. } => {
                 // Evaluates RHS first
-                let rvalue = eval_expr(&rvalue, vars, funcs)?;
-                // Pushes name into variable symbol table
-                // TODO shadones (se já tiver no vec, só coloca no final)
-                vars.last_mut()
-                    .unwrap()
-                    .insert(lvalue.clone(), vec![rvalue.clone()]);
-                Ok(rvalue)
+                let rvalue = eval_expr(rvalue, vars, funcs)?;
+                // Pushes name into variable symbol table, shadowing any variable with the same name
+                vars.declare(lvalue.clone(), rvalue.clone());
+                rvalue
             }
-            Statement::Assign { lvalue, rvalue } => {
-                let new_value = eval_expr(&rvalue, vars, funcs)?;
-                vars.last_mut()
-                    .unwrap()
-                    // TODO shadones
-                    .insert(lvalue.clone(), vec![new_value.clone()]);
-                Ok(new_value)
+            Statement::Assign { lvalue, op, rvalue } => {
+                eval_assign(lvalue, *op, rvalue, vars, funcs)?
             }
-            Statement::Null => Ok(Literal::Null),
-        });
-        match last_statement {
-            Some(Ok(Literal::Break)) => break,
-            _ => continue,
-        }
+            Statement::Null => Literal::Null,
+        };
     }
-    Ok(last_statement.unwrap().unwrap())
+    Ok(last_statement)
+}
+
+/// Evaluates source string using [`parser!()`].
+/// Runs on a thread of its own, with a stack big enough for [NESTING_LIMIT] nested blocks and expressions, whatever the caller's stack is
+pub fn eval_source(src: String) -> Result<Literal, Vec<Error>> {
+    std::thread::scope(|scope| {
+        match std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || run_source(&src))
+        {
+            Ok(thread) => thread
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic)),
+            // Not being able to spawn threads is no reason to not evaluate at all
+            Err(_) => run_source(&src),
+        }
+    })
 }
 
-/// Evaluates source string using [`parser!()`]
-pub fn eval_source(src: String) -> Result<Literal, Vec<String>> {
+/// Parses and evaluates `src`, on the current thread
+fn run_source(src: &str) -> Result<Literal, Vec<Error>> {
+    let src_len = src.chars().count();
+    if let Some(span) = parser::nesting_over(src, PARSE_NESTING_LIMIT) {
+        return Err(vec![Error::NestingLimit {
+            limit: PARSE_NESTING_LIMIT,
+            span,
+        }]);
+    }
     match parser!().then_ignore(end()).parse_recovery_verbose(src) {
         // Extract `main()` function
-        (Some(ast), _) => {
+        (Some(ast), parse_errs) if parse_errs.is_empty() => {
+            let errs = check::mutability(&ast);
+            if !errs.is_empty() {
+                return Err(errs);
+            }
             // Register all function items
             let mut funcs: HashMap<String, &Function> = HashMap::new();
             for item in ast.iter() {
-                match item {
+                match &item.node {
                     Item::Function(f) => funcs.insert(f.name.clone(), &Box::new(f)),
                 };
             }
             // Searching for function called `main`
             if let Some(main) = funcs.get("main") {
                 // Evaluate `main(){ }
-                match eval(&main.body, &mut vec![HashMap::new()], &funcs, false) {
-                    Ok(output) => Ok(output),
-                    Err(eval_err) => Err(vec![format!("Evaluation error: {:?}", eval_err)]),
-                }
+                function_output(eval(&main.body, &mut Scopes::default(), &funcs))
+                    .map_err(|eval_err| vec![eval_err])
             } else {
-                Err(vec![
-                    "Syntax error: No function named `main` in top-level items. Can't continue."
-                        .to_string(),
-                ])
+                Err(vec![Error::NoMain { span: 0..src_len }])
             }
         }
-        (None, parse_errs) => Err(parse_errs
-            .into_iter()
-            .map(|e| format!("Parse error: {:?}", e))
-            .collect()),
+        (_, parse_errs) => Err(parse_errs.into_iter().map(Error::from).collect()),
     }
 }
@@ -1,12 +1,22 @@
-use zeca::eval_source;
+use std::io::IsTerminal;
+
+use zeca::{eval_source, parser::ast::Literal, report::write_reports};
 
 pub fn main() {
-    let src = std::fs::read_to_string(
-        std::env::args()
-            .nth(1)
-            .expect("Please provide a file to parse and interpret"),
-    )
-    .expect("Error converting file contents to string");
-    let result = eval_source(src).unwrap();
-    println!("{:?}", result);
+    let filename = std::env::args()
+        .nth(1)
+        .expect("Please provide a file to parse and interpret");
+    let src = std::fs::read_to_string(&filename).expect("Error converting file contents to string");
+    match eval_source(src.clone()) {
+        // Programs that only print evaluate to `()`, which isn't worth showing
+        Ok(Literal::Null) => (),
+        Ok(result) => println!("{}", result),
+        Err(errs) => {
+            // No colors when reports are redirected to a file or a pipe
+            let color = std::io::stderr().is_terminal();
+            write_reports(&errs, &filename, &src, color, std::io::stderr())
+                .expect("Error writing error reports");
+            std::process::exit(1);
+        }
+    }
 }
@@ -0,0 +1,117 @@
+//! Built-in methods, called on values as in `s.len()`
+//!
+//! `str`s have `len`, `contains`, `split`, `trim`, `to_uppercase`, `to_lowercase`, `replace` and `parse`,
+//! with `to_upper` and `to_lower` as shorter names for the case conversions. Arrays have `len`
+
+use chumsky::{prelude::end, Parser};
+
+use crate::error::{Error, Span};
+use crate::parser::{ast::*, number_parser};
+use crate::{type_mismatch, Unwind};
+
+/// Number of arguments `method` takes, or `None` if values like `receiver` have no such method
+fn arity(receiver: &Literal, method: &str) -> Option<usize> {
+    match (receiver, method) {
+        (Literal::Str(_) | Literal::Array(_), "len") => Some(0),
+        (
+            Literal::Str(_),
+            "trim" | "to_uppercase" | "to_upper" | "to_lowercase" | "to_lower" | "parse",
+        ) => Some(0),
+        (Literal::Str(_), "contains" | "split") => Some(1),
+        (Literal::Str(_), "replace") => Some(2),
+        _ => None,
+    }
+}
+
+/// Calls `method` on `receiver`, with its arguments already evaluated. `span` is the whole method call
+pub(crate) fn call_method(
+    receiver: Literal,
+    method: &Spanned<String>,
+    args: Vec<Spanned<Literal>>,
+    span: &Span,
+) -> Result<Literal, Unwind> {
+    let undefined = |receiver: &Literal| Error::UndefinedMethod {
+        name: method.node.clone(),
+        type_name: receiver.type_name().to_string(),
+        span: method.span.clone(),
+    };
+    let expected = arity(&receiver, &method.node).ok_or_else(|| undefined(&receiver))?;
+    if args.len() != expected {
+        return Err(Error::ArityMismatch {
+            name: method.node.clone(),
+            expected,
+            found: args.len(),
+            span: span.clone(),
+        }
+        .into());
+    }
+
+    match (receiver, method.node.as_str(), args.as_slice()) {
+        // Lengths are in `char`s, the same unit `str`s are indexed by
+        (Literal::Str(string), "len", []) => Ok(length(string.chars().count())),
+        (Literal::Array(array), "len", []) => Ok(length(array.len())),
+        (Literal::Str(string), "contains", [pattern]) => {
+            Ok(Literal::Bool(string.contains(&text(pattern)?)))
+        }
+        (Literal::Str(string), "split", [pattern]) => Ok(Literal::Array(
+            string
+                .split(&text(pattern)?)
+                .map(|part| Literal::Str(part.to_string()))
+                .collect(),
+        )),
+        (Literal::Str(string), "trim", []) => Ok(Literal::Str(string.trim().to_string())),
+        (Literal::Str(string), "to_uppercase" | "to_upper", []) => {
+            Ok(Literal::Str(string.to_uppercase()))
+        }
+        (Literal::Str(string), "to_lowercase" | "to_lower", []) => {
+            Ok(Literal::Str(string.to_lowercase()))
+        }
+        (Literal::Str(string), "replace", [from, to]) => {
+            Ok(Literal::Str(string.replace(&text(from)?, &text(to)?)))
+        }
+        (Literal::Str(string), "parse", []) => parse(&string).ok_or_else(|| {
+            Error::UnparsableValue {
+                text: string,
+                span: span.clone(),
+            }
+            .into()
+        }),
+        (receiver, ..) => Err(undefined(&receiver).into()),
+    }
+}
+
+/// A length or count, which is a `usize` as in Rust
+fn length(len: usize) -> Literal {
+    Literal::Num(Number::UInteger(len))
+}
+
+/// Text taken by methods that work with patterns or replacements, which can be a `str` or a `char`
+fn text(arg: &Spanned<Literal>) -> Result<String, Unwind> {
+    match &arg.node {
+        Literal::Str(string) => Ok(string.clone()),
+        Literal::Char(c) => Ok(c.to_string()),
+        value => Err(type_mismatch("a `str` or `char`", value, &arg.span)),
+    }
+}
+
+/// Parses `text` as a `bool`, or as a number literal with an optional `-` before it, as in `-1.5` or `0xffu8`
+fn parse(text: &str) -> Option<Literal> {
+    match text {
+        "true" => return Some(Literal::Bool(true)),
+        "false" => return Some(Literal::Bool(false)),
+        _ => (),
+    }
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let number = match number_parser().then_ignore(end()).parse(digits) {
+        Ok(Expr::Literal(Literal::Num(number))) => number,
+        _ => return None,
+    };
+    match negative {
+        true if number.number_type().is_unsigned() => None,
+        true => number.checked_neg().map(Literal::Num),
+        false => Some(Literal::Num(number)),
+    }
+}
@@ -1,5 +1,33 @@
 //! The AST for the parser to use
 
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use crate::error::Span;
+
+/// A node of the AST, along with the [Span] of the source it was parsed from
+#[derive(Debug, Clone)]
+pub struct Spanned<T> {
+    /// The node itself
+    pub node: T,
+    /// Where in the source the node is
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    /// Wraps `node`, parsed from `span`. Argument order matches [`map_with_span()`][chumsky::Parser::map_with_span()]
+    pub fn new(node: T, span: Span) -> Self {
+        Self { node, span }
+    }
+}
+
+impl<T> std::ops::Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.node
+    }
+}
+
 /// Return values for ZECA
 #[derive(Debug, PartialEq, PartialOrd, Clone)]
 pub enum Literal {
@@ -11,79 +39,575 @@ pub enum Literal {
     Bool(bool),
     /// String value
     Str(String),
-    /// Function variables
+    /// A single Unicode character, as in `'a'`
+    Char(char),
+    /// Function values: closures, and function items used as values
     Fn(Function),
     /// Array of literals
     Array(Vec<Literal>),
-    /// Break special value
-    Break,
+    /// Range of integers, `start..end`, or `start..=end` if it is `inclusive`
+    Range {
+        /// First integer in the range
+        start: isize,
+        /// Integer the range stops at. Only part of the range if it is `inclusive`
+        end: isize,
+        /// Whether `end` is part of the range
+        inclusive: bool,
+    },
 }
 
+impl Literal {
+    /// Name of the type of this value, as used in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Null => "()",
+            Self::Num(n) => n.number_type().name(),
+            Self::Bool(_) => "bool",
+            Self::Str(_) => "str",
+            Self::Char(_) => "char",
+            Self::Fn(_) => "fn",
+            Self::Array(_) => "array",
+            Self::Range { .. } => "range",
+        }
+    }
+}
+
+/// Formats values like Rust's `{}` does. Arrays and ranges, which have no such format in Rust, are formatted like
+/// [`{:?}`][Literal::debug()] formats them. Precision, as in `{:.2}`, is passed on to numbers and `str`s
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => f.write_str("()"),
+            Self::Num(n) => fmt::Display::fmt(n, f),
+            Self::Bool(b) => fmt::Display::fmt(b, f),
+            Self::Str(s) => fmt::Display::fmt(s.as_str(), f),
+            Self::Char(c) => fmt::Display::fmt(c, f),
+            Self::Fn(function) => write!(f, "fn {}({})", function.name, function.args.join(", ")),
+            Self::Array(_) | Self::Range { .. } => fmt::Display::fmt(&self.debug(), f),
+        }
+    }
+}
+
+impl Literal {
+    /// Formats the value like Rust's `{:?}` does: `str`s and `char`s quoted and escaped, and floats always with a
+    /// fractional part, as in `1.0`
+    pub fn debug(&self) -> impl fmt::Display + '_ {
+        DebugLiteral(self)
+    }
+}
+
+/// A [Literal] formatted with [Literal::debug()]
+struct DebugLiteral<'a>(&'a Literal);
+
+impl fmt::Display for DebugLiteral<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Literal::Num(Number::Float(x) | Number::F64(x)) => fmt::Debug::fmt(x, f),
+            Literal::Num(Number::F32(x)) => fmt::Debug::fmt(x, f),
+            Literal::Str(s) => fmt::Debug::fmt(s, f),
+            Literal::Char(c) => fmt::Debug::fmt(c, f),
+            Literal::Array(array) => {
+                f.write_str("[")?;
+                for (i, element) in array.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    fmt::Display::fmt(&element.debug(), f)?;
+                }
+                f.write_str("]")
+            }
+            Literal::Range {
+                start,
+                end,
+                inclusive,
+            } => write!(f, "{}..{}{}", start, if *inclusive { "=" } else { "" }, end),
+            literal => fmt::Display::fmt(literal, f),
+        }
+    }
+}
+
+/// Constructor of an [Expr] with two operands, as in `Expr::Add`
+pub type BinaryOp = fn(Box<Spanned<Expr>>, Box<Spanned<Expr>>) -> Expr;
+
 /// Types for ZECA's expressions. Uses mostly native Rust types
 #[derive(Debug, Clone)]
 pub enum Expr {
     /// Literals values used within `Expr`s , e.g **1** + **1**
     Literal(Literal),
 
-    /// Negation expression. Both things like `-1` and `!true`
-    Neg(Box<Expr>),
+    /// Negation expression. Both things like `-1` and `-true`
+    Neg(Box<Spanned<Expr>>),
+    /// Unary !. Logical not of `bool`s and bitwise not of integers
+    Not(Box<Spanned<Expr>>),
 
-    /// Binary +
-    Add(Box<Expr>, Box<Expr>),
+    /// Binary +. Also concatenates `str`s
+    Add(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
     /// Binary -
-    Sub(Box<Expr>, Box<Expr>),
+    Sub(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
     /// Binary *
-    Mul(Box<Expr>, Box<Expr>),
+    Mul(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
     /// Binary /
-    Div(Box<Expr>, Box<Expr>),
+    Div(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    /// Binary %. Remainder of the division, with the sign of the dividend
+    Rem(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    /// Binary **. Exponentiation, which is right-associative
+    Pow(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+
+    /// Binary &. Bitwise and of integers, or non-short-circuiting and of `bool`s
+    BitAnd(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    /// Binary |. Bitwise or of integers, or non-short-circuiting or of `bool`s
+    BitOr(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    /// Binary ^. Bitwise xor of integers, or xor of `bool`s
+    BitXor(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    /// Binary <<
+    Shl(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    /// Binary >>. Arithmetic shift for signed integers
+    Shr(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
 
     /// Expr1 < Expr2
-    Lt(Box<Expr>, Box<Expr>),
+    Lt(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
     /// Expr1 > Expr2
-    Gt(Box<Expr>, Box<Expr>),
+    Gt(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    /// Expr1 <= Expr2
+    Le(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    /// Expr1 >= Expr2
+    Ge(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
     /// Expr1 == Expr2
-    Eq(Box<Expr>, Box<Expr>),
-    /// Expr1 && Expr2. >0 is truthy
-    And(Box<Expr>, Box<Expr>),
-    /// Expr1 || Expr2. >0 is truthy
-    Or(Box<Expr>, Box<Expr>),
+    Eq(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    /// Expr1 != Expr2
+    Ne(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    /// Expr1 && Expr2. Only takes `bool`s, and Expr2 is not evaluated if Expr1 is `false`
+    And(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    /// Expr1 || Expr2. Only takes `bool`s, and Expr2 is not evaluated if Expr1 is `true`
+    Or(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+
+    /// Expr1..Expr2. Range from Expr1 up to, but not including, Expr2
+    Range(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    /// Expr1..=Expr2. Range from Expr1 up to and including Expr2
+    RangeInclusive(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+
+    /// Expr as Type. Conversion between numeric types, or from `bool` to an integer
+    Cast(Box<Spanned<Expr>>, NumberType),
 
     /// Declare an array of expressions
-    Array(Vec<Expr>),
-
-    /// Function call expression. `()` operator placed after a symbol, as in `foo()`
-    Call(String, Vec<Expr>),
-    /// Variable invocation. Index is for array variable
-    Var {
-        /// Name of the variable
-        name: String,
-        /// Index offset from start of the array. If `[]` is not used, this is defaulted to `0`.
-        index: Box<Expr>,
+    Array(Vec<Spanned<Expr>>),
+
+    /// A [Loop] used as an expression. Evaluates to the value given to `break`
+    Loop(Box<Loop>),
+
+    /// Function call expression. `()` operator placed after anything that evaluates to a function, as in `foo()` or `fs[0](x)`
+    Call(Box<Spanned<Expr>>, Vec<Spanned<Expr>>),
+    /// Formatting macro, as in `println!("{} {x:>5}", a)`. The format string is already split into its pieces
+    Macro {
+        /// Which of the formatting macros it is
+        r#macro: Macro,
+        /// Text and arguments to format, in order
+        format: Vec<FormatPiece>,
+        /// Arguments referred to by the format string: the positional ones, then the named ones, then the variables
+        /// it uses by name, as in `{x}`
+        args: Vec<Spanned<Expr>>,
     },
+    /// Built-in method call, as in `s.len()` or `s.replace("a", "b")`
+    MethodCall {
+        /// Value the method is called on
+        receiver: Box<Spanned<Expr>>,
+        /// Name of the method
+        method: Spanned<String>,
+        /// Arguments passed to the method
+        args: Vec<Spanned<Expr>>,
+    },
+    /// Closure expression, as in `|x| x + 1` or `|| { ... }`. Evaluates to a [Literal::Fn]
+    Closure {
+        /// Names of the arguments
+        args: Vec<String>,
+        /// Body of the closure. A body that is a single expression is a block with only that expression
+        body: Box<Spanned<Block>>,
+        /// Variables the body uses from around the closure, which are the ones it captures
+        captures: Vec<String>,
+    },
+    /// Variable invocation
+    Var(String),
+    /// Expr1[Expr2]. Element of an array, or `char` of a `str`, at an integer index counted from `0`.
+    /// Indexing with a range, as in `s[1..3]`, takes a slice of the array or `str` instead
+    Index(Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+}
+
+/// Drops the expressions this one is made of one after the other, instead of recursively.
+/// Chains of operators, as in `1 + 1 + ... + 1`, nest as deep as they are long, and would overflow the stack
+impl Drop for Expr {
+    fn drop(&mut self) {
+        let mut operands = Vec::new();
+        self.take_operands(&mut operands);
+        while let Some(mut expr) = operands.pop() {
+            expr.take_operands(&mut operands);
+        }
+    }
+}
+
+impl Expr {
+    /// Moves the expressions this one is made of to `operands`, leaving `()`s in their place.
+    /// Blocks are left in place, as their nesting is limited by [PARSE_NESTING_LIMIT][crate::PARSE_NESTING_LIMIT]
+    fn take_operands(&mut self, operands: &mut Vec<Expr>) {
+        let mut take = |expr: &mut Spanned<Expr>| {
+            operands.push(std::mem::replace(
+                &mut expr.node,
+                Expr::Literal(Literal::Null),
+            ));
+        };
+        match self {
+            Expr::Literal(_) | Expr::Var(_) | Expr::Closure { .. } | Expr::Loop(_) => (),
+            Expr::Neg(a) | Expr::Not(a) | Expr::Cast(a, _) => take(a),
+            Expr::Add(a, b)
+            | Expr::Sub(a, b)
+            | Expr::Mul(a, b)
+            | Expr::Div(a, b)
+            | Expr::Rem(a, b)
+            | Expr::Pow(a, b)
+            | Expr::BitAnd(a, b)
+            | Expr::BitOr(a, b)
+            | Expr::BitXor(a, b)
+            | Expr::Shl(a, b)
+            | Expr::Shr(a, b)
+            | Expr::Lt(a, b)
+            | Expr::Gt(a, b)
+            | Expr::Le(a, b)
+            | Expr::Ge(a, b)
+            | Expr::Eq(a, b)
+            | Expr::Ne(a, b)
+            | Expr::And(a, b)
+            | Expr::Or(a, b)
+            | Expr::Range(a, b)
+            | Expr::RangeInclusive(a, b)
+            | Expr::Index(a, b) => {
+                take(a);
+                take(b);
+            }
+            Expr::Array(exprs) | Expr::Macro { args: exprs, .. } => exprs.iter_mut().for_each(take),
+            Expr::Call(callee, args)
+            | Expr::MethodCall {
+                receiver: callee,
+                args,
+                ..
+            } => {
+                take(callee);
+                args.iter_mut().for_each(take);
+            }
+        }
+    }
 }
 
 /// Types for ZECA's expressions. Uses mostly native Rust types
 #[derive(Debug, PartialEq, PartialOrd, Copy, Clone)]
 pub enum Number {
-    /// Fake numbers. -1, 0, 1
+    /// Fake numbers. -1, 0, 1. Integer literals without a suffix are these, and adapt to the type of any number they are used with
     Integer(isize),
+    /// `isize`, written as such
+    Isize(isize),
     /// Unsigned integer numbers. 0, 1, 2
-    // UInteger(usize),
-    /// Real numbers
+    UInteger(usize),
+    /// `i8`
+    I8(i8),
+    /// `i16`
+    I16(i16),
+    /// `i32`
+    I32(i32),
+    /// `i64`
+    I64(i64),
+    /// `u8`
+    U8(u8),
+    /// `u16`
+    U16(u16),
+    /// `u32`
+    U32(u32),
+    /// `u64`
+    U64(u64),
+    /// Real numbers. Float literals without a suffix are these, and adapt to the type of floats they are used with
     Float(f64),
+    /// `f64`, written as such
+    F64(f64),
+    /// `f32`
+    F32(f32),
 }
 
-impl std::ops::Neg for Number {
-    type Output = Self;
+/// Formats numbers like Rust does. Precision, as in `{:.2}`, is passed on to floats
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Integer(x) | Self::Isize(x) => fmt::Display::fmt(x, f),
+            Self::UInteger(x) => fmt::Display::fmt(x, f),
+            Self::I8(x) => fmt::Display::fmt(x, f),
+            Self::I16(x) => fmt::Display::fmt(x, f),
+            Self::I32(x) => fmt::Display::fmt(x, f),
+            Self::I64(x) => fmt::Display::fmt(x, f),
+            Self::U8(x) => fmt::Display::fmt(x, f),
+            Self::U16(x) => fmt::Display::fmt(x, f),
+            Self::U32(x) => fmt::Display::fmt(x, f),
+            Self::U64(x) => fmt::Display::fmt(x, f),
+            Self::Float(x) | Self::F64(x) => fmt::Display::fmt(x, f),
+            Self::F32(x) => fmt::Display::fmt(x, f),
+        }
+    }
+}
+
+/// Types of [Number]s, as named in `as` casts and literal suffixes
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum NumberType {
+    /// Integer without a suffix, for [Number::Integer]. Named `isize`, the type it has on its own
+    Integer,
+    /// `isize`, for [Number::Isize]
+    Isize,
+    /// `usize`, for [Number::UInteger]
+    UInteger,
+    /// `i8`
+    I8,
+    /// `i16`
+    I16,
+    /// `i32`
+    I32,
+    /// `i64`
+    I64,
+    /// `u8`
+    U8,
+    /// `u16`
+    U16,
+    /// `u32`
+    U32,
+    /// `u64`
+    U64,
+    /// Float without a suffix, for [Number::Float]. Named `f64`, the type it has on its own
+    Float,
+    /// `f64`, for [Number::F64]
+    F64,
+    /// `f32`
+    F32,
+}
+
+impl NumberType {
+    /// Every type that can be named, along with its name
+    const NAMES: [(Self, &'static str); 12] = [
+        (Self::Isize, "isize"),
+        (Self::UInteger, "usize"),
+        (Self::I8, "i8"),
+        (Self::I16, "i16"),
+        (Self::I32, "i32"),
+        (Self::I64, "i64"),
+        (Self::U8, "u8"),
+        (Self::U16, "u16"),
+        (Self::U32, "u32"),
+        (Self::U64, "u64"),
+        (Self::F64, "f64"),
+        (Self::F32, "f32"),
+    ];
+
+    /// Name of the type, as written in source
+    pub fn name(self) -> &'static str {
+        let ty = match self {
+            Self::Integer => Self::Isize,
+            Self::Float => Self::F64,
+            ty => ty,
+        };
+        Self::NAMES
+            .iter()
+            .find_map(|&(named, name)| (named == ty).then_some(name))
+            .unwrap_or_default()
+    }
 
-    fn neg(self) -> Self::Output {
+    /// Type called `name` in source, if any
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .find_map(|&(ty, ty_name)| (ty_name == name).then_some(ty))
+    }
+
+    /// Whether this is an unsigned integer type, which can't be negated
+    pub fn is_unsigned(self) -> bool {
+        matches!(
+            self,
+            Self::UInteger | Self::U8 | Self::U16 | Self::U32 | Self::U64
+        )
+    }
+
+    /// Whether this is a floating-point type
+    pub fn is_float(self) -> bool {
+        matches!(self, Self::Float | Self::F64 | Self::F32)
+    }
+
+    /// Whether this is the type of a literal without a suffix, which adapts to the numbers it is used with
+    pub fn is_untyped(self) -> bool {
+        matches!(self, Self::Integer | Self::Float)
+    }
+}
+
+/// Builds a [Number] of type `$to` out of `$x`, using Rust's `as`
+macro_rules! number_as {
+    ($x:expr, $to:expr) => {
+        match $to {
+            NumberType::Integer => Number::Integer($x as isize),
+            NumberType::Isize => Number::Isize($x as isize),
+            NumberType::UInteger => Number::UInteger($x as usize),
+            NumberType::I8 => Number::I8($x as i8),
+            NumberType::I16 => Number::I16($x as i16),
+            NumberType::I32 => Number::I32($x as i32),
+            NumberType::I64 => Number::I64($x as i64),
+            NumberType::U8 => Number::U8($x as u8),
+            NumberType::U16 => Number::U16($x as u16),
+            NumberType::U32 => Number::U32($x as u32),
+            NumberType::U64 => Number::U64($x as u64),
+            NumberType::Float => Number::Float($x as f64),
+            NumberType::F64 => Number::F64($x as f64),
+            NumberType::F32 => Number::F32($x as f32),
+        }
+    };
+}
+
+/// Evaluates `$body` with the value of the integer `$num` bound to `$x`, wrapping the `Option` it returns back into
+/// the same type of [Number]. Floats give `None`
+macro_rules! map_integer {
+    ($num:expr, $x:ident => $body:expr) => {
+        match $num {
+            Number::Integer($x) => $body.map(Number::Integer),
+            Number::Isize($x) => $body.map(Number::Isize),
+            Number::UInteger($x) => $body.map(Number::UInteger),
+            Number::I8($x) => $body.map(Number::I8),
+            Number::I16($x) => $body.map(Number::I16),
+            Number::I32($x) => $body.map(Number::I32),
+            Number::I64($x) => $body.map(Number::I64),
+            Number::U8($x) => $body.map(Number::U8),
+            Number::U16($x) => $body.map(Number::U16),
+            Number::U32($x) => $body.map(Number::U32),
+            Number::U64($x) => $body.map(Number::U64),
+            Number::Float(_) | Number::F64(_) | Number::F32(_) => None,
+        }
+    };
+}
+
+impl Number {
+    /// Type of this number
+    pub fn number_type(&self) -> NumberType {
         match self {
-            Self::Integer(x) => Self::Integer(-x),
-            // Notice the casting
-            // Self::UInteger(x) => Self::Integer(-(x as isize)),
-            Self::Float(x) => Self::Float(-x),
+            Self::Integer(_) => NumberType::Integer,
+            Self::Isize(_) => NumberType::Isize,
+            Self::UInteger(_) => NumberType::UInteger,
+            Self::I8(_) => NumberType::I8,
+            Self::I16(_) => NumberType::I16,
+            Self::I32(_) => NumberType::I32,
+            Self::I64(_) => NumberType::I64,
+            Self::U8(_) => NumberType::U8,
+            Self::U16(_) => NumberType::U16,
+            Self::U32(_) => NumberType::U32,
+            Self::U64(_) => NumberType::U64,
+            Self::Float(_) => NumberType::Float,
+            Self::F64(_) => NumberType::F64,
+            Self::F32(_) => NumberType::F32,
+        }
+    }
+
+    /// Value of an integer, in a type wide enough for all of them. `None` for floats
+    fn as_i128(self) -> Option<i128> {
+        match self {
+            Self::Integer(x) | Self::Isize(x) => Some(x as i128),
+            Self::UInteger(x) => Some(x as i128),
+            Self::I8(x) => Some(x as i128),
+            Self::I16(x) => Some(x as i128),
+            Self::I32(x) => Some(x as i128),
+            Self::I64(x) => Some(x as i128),
+            Self::U8(x) => Some(x as i128),
+            Self::U16(x) => Some(x as i128),
+            Self::U32(x) => Some(x as i128),
+            Self::U64(x) => Some(x as i128),
+            Self::Float(_) | Self::F64(_) | Self::F32(_) => None,
+        }
+    }
+
+    /// Converts to `to`, with the same semantics as Rust's `as`:
+    /// integers wrap around when they don't fit, and floats are truncated and saturated when cast to integers
+    pub fn cast(self, to: NumberType) -> Self {
+        match (self, self.as_i128()) {
+            (Self::Float(x) | Self::F64(x), _) => number_as!(x, to),
+            (Self::F32(x), _) => number_as!(x, to),
+            (_, Some(x)) => number_as!(x, to),
+            // Every number but floats has a `as_i128()`
+            (_, None) => self,
+        }
+    }
+
+    /// Converts an integer to the integer type `to`, if the value fits in it, like Rust's `TryFrom`
+    pub fn try_cast(self, to: NumberType) -> Option<Self> {
+        let cast = self.cast(to);
+        match (self.as_i128(), cast.as_i128()) {
+            (Some(x), Some(y)) if x == y => Some(cast),
+            _ => None,
         }
     }
+
+    /// Associated constant of the type `ty`, as in `f64::NAN` or `u8::MAX`
+    pub fn constant(ty: NumberType, name: &str) -> Option<Self> {
+        macro_rules! constants {
+            ($($ty:ident => $variant:ident: [$($name:ident),*]),* $(,)?) => {
+                match (ty, name) {
+                    $($((NumberType::$variant, stringify!($name)) => Some(Self::$variant($ty::$name)),)*)*
+                    _ => None,
+                }
+            };
+        }
+        constants!(
+            isize => Isize: [MIN, MAX],
+            usize => UInteger: [MIN, MAX],
+            i8 => I8: [MIN, MAX],
+            i16 => I16: [MIN, MAX],
+            i32 => I32: [MIN, MAX],
+            i64 => I64: [MIN, MAX],
+            u8 => U8: [MIN, MAX],
+            u16 => U16: [MIN, MAX],
+            u32 => U32: [MIN, MAX],
+            u64 => U64: [MIN, MAX],
+            f64 => F64: [MIN, MAX, EPSILON, INFINITY, NEG_INFINITY, NAN],
+            f32 => F32: [MIN, MAX, EPSILON, INFINITY, NEG_INFINITY, NAN],
+        )
+    }
+
+    /// Whether this is the integer `0`. Floats never are, as dividing them by zero is fine
+    pub fn is_integer_zero(&self) -> bool {
+        self.as_i128() == Some(0)
+    }
+
+    /// Negates a signed number, returning `None` on overflow or for unsigned integers
+    pub fn checked_neg(self) -> Option<Self> {
+        match self {
+            Self::Integer(x) => x.checked_neg().map(Self::Integer),
+            Self::Isize(x) => x.checked_neg().map(Self::Isize),
+            Self::I8(x) => x.checked_neg().map(Self::I8),
+            Self::I16(x) => x.checked_neg().map(Self::I16),
+            Self::I32(x) => x.checked_neg().map(Self::I32),
+            Self::I64(x) => x.checked_neg().map(Self::I64),
+            Self::Float(x) => Some(Self::Float(-x)),
+            Self::F64(x) => Some(Self::F64(-x)),
+            Self::F32(x) => Some(Self::F32(-x)),
+            Self::UInteger(_) | Self::U8(_) | Self::U16(_) | Self::U32(_) | Self::U64(_) => None,
+        }
+    }
+
+    /// Flips every bit of an integer. `None` for floats
+    pub fn bitwise_not(self) -> Option<Self> {
+        map_integer!(self, x => Some(!x))
+    }
+
+    /// Raises an integer to the power of `exp`, returning `None` on overflow or for floats
+    pub fn checked_pow(self, exp: u32) -> Option<Self> {
+        map_integer!(self, x => x.checked_pow(exp))
+    }
+
+    /// Shifts the bits of an integer `rhs` places to the left.
+    /// Returns `None` if `rhs` is not smaller than the number of bits in the type, or for floats
+    pub fn checked_shl(self, rhs: u32) -> Option<Self> {
+        map_integer!(self, x => x.checked_shl(rhs))
+    }
+
+    /// Shifts the bits of an integer `rhs` places to the right. Signed integers keep their sign.
+    /// Returns `None` if `rhs` is not smaller than the number of bits in the type, or for floats
+    pub fn checked_shr(self, rhs: u32) -> Option<Self> {
+        map_integer!(self, x => x.checked_shr(rhs))
+    }
 }
 
 /// A function declaration
@@ -95,7 +619,30 @@ pub struct Function {
     /// List of function argument names
     pub args: Vec<String>,
     /// Function body, a block of statements
-    pub body: Box<Block>,
+    pub body: Box<Spanned<Block>>,
+    /// What the function can reach besides its arguments. Empty for declared functions, until they are used as values
+    pub captures: Arc<Captures>,
+}
+
+/// Name given to every closure, as they have none in source
+pub const CLOSURE_NAME: &str = "{closure}";
+
+/// What a function value carries from where it was created
+#[derive(Debug, Clone, Default)]
+pub struct Captures {
+    /// Variables a closure uses from where it was created, copied by value
+    pub vars: HashMap<String, Literal>,
+    /// Function items in scope where the function was created or declared
+    pub items: Option<Arc<Items>>,
+}
+
+/// Function items declared in a block. Functions declared at the top level are not here
+#[derive(Debug)]
+pub struct Items {
+    /// Functions declared in the block, by name
+    pub functions: HashMap<String, Arc<Function>>,
+    /// Items of the enclosing block, if it has any, or of the block enclosing it, and so on
+    pub parent: Option<Arc<Items>>,
 }
 
 impl PartialEq for Function {
@@ -110,6 +657,77 @@ impl PartialOrd for Function {
     }
 }
 
+/// Formatting macros, which all take a format string and its arguments, as in Rust's `std::fmt`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Macro {
+    /// `print!`, writes to the standard output
+    Print,
+    /// `println!`, writes a line to the standard output
+    Println,
+    /// `eprint!`, writes to the standard error
+    Eprint,
+    /// `eprintln!`, writes a line to the standard error
+    Eprintln,
+    /// `format!`, evaluates to the formatted `str`
+    Format,
+}
+
+/// Piece of a format string
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormatPiece {
+    /// Text written as is, with `{{` and `}}` already turned into `{` and `}`
+    Text(String),
+    /// A `{...}` replaced by the argument at index `arg` of the macro, formatted as `spec` says
+    Arg {
+        /// Index of the argument in the macro's arguments
+        arg: usize,
+        /// How to format the argument
+        spec: FormatSpec,
+    },
+}
+
+/// How to format an argument, as in the `:>8.2?` of `{x:>8.2?}`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatSpec {
+    /// Character used to pad the argument up to `width`. Spaces by default
+    pub fill: char,
+    /// Where the argument goes when padded. Numbers go right by default, anything else goes left
+    pub align: Option<Align>,
+    /// `+`, shows the sign of positive numbers too
+    pub sign: bool,
+    /// `0`, pads numbers with zeros after their sign, instead of with `fill`
+    pub zero: bool,
+    /// Minimum number of `char`s to take
+    pub width: Option<Count>,
+    /// Digits after the point for floats, or maximum number of `char`s for `str`s
+    pub precision: Option<Count>,
+    /// `?`, formats with [Literal::debug()] instead of [Display][fmt::Display]
+    pub debug: bool,
+}
+
+/// Alignment of a padded argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    /// `<`
+    Left,
+    /// `^`
+    Center,
+    /// `>`
+    Right,
+}
+
+/// Largest width or precision of a [FormatSpec], as in Rust
+pub const MAX_COUNT: usize = u16::MAX as usize;
+
+/// Width or precision of a [FormatSpec]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Count {
+    /// Written in the format string, as in `{:5}`
+    Is(usize),
+    /// Taken from the argument at this index, as in `{:1$}`, `{:width$}` or `{:.*}`
+    Arg(usize),
+}
+
 /// Top-level constructs. Declared "with no indentation"
 #[derive(Debug, Clone)]
 pub enum Item {
@@ -123,44 +741,102 @@ pub enum Statement {
     /// A null statement (contains only `;`)
     Null,
     /// A Break statement. Breaks out of [Loop]s.
-    Break,
+    Break {
+        /// Label of the loop to break out of. If `None`, breaks out of the innermost loop
+        label: Option<String>,
+        /// Value the loop evaluates to. Only allowed on [Loop], not on [While] or [For]
+        value: Option<Box<Spanned<Expr>>>,
+    },
+    /// A Continue statement. Skips to the next iteration of [Loop]s.
+    Continue {
+        /// Label of the loop to continue. If `None`, continues the innermost loop
+        label: Option<String>,
+    },
+    /// A Return statement. Exits the current function, optionally with a value
+    Return(Option<Box<Spanned<Expr>>>),
     /// A item construct. Those can be placed wherever a statement can
     Item(Box<Item>),
     /// Conditional execution. It Expr is true, executes first block, else executes second block
     Conditional {
         /// The expression to check for it will jump or not
-        r#if: Box<Expr>,
+        r#if: Box<Spanned<Expr>>,
         /// Jumps to this if `r#if`[Statement::Conditional.r#if] is true
-        r#then: Box<Block>,
+        r#then: Box<Spanned<Block>>,
         /// Jumps to this if `r#if`[Statement::Conditional.r#if] is false. Is also optional, and will keep execution as normal if field is `None`
-        r#else: Option<Box<Block>>,
+        r#else: Option<Box<Spanned<Block>>>,
     },
     /// Variable declaration
     Let {
+        /// Whether the variable was declared with `let mut`, and so can be assigned to
+        mutable: bool,
         /// Name defined to this symbol
         lvalue: String,
         /// Value to be assigned to symbol
-        rvalue: Box<Expr>,
+        rvalue: Box<Spanned<Expr>>,
     },
-    /// Variable assignment
+    /// Variable assignment, either plain (`x = 1;`) or compound (`x += 1;`)
     Assign {
-        /// Name defined to this symbol
-        lvalue: String,
+        /// Place the value is written to
+        lvalue: Spanned<Place>,
+        /// Operator of a compound assignment, applied to the current value and `rvalue`. `None` for plain assignments
+        op: Option<BinaryOp>,
         /// Value to be assigned to symbol
-        rvalue: Box<Expr>,
+        rvalue: Box<Spanned<Expr>>,
     },
     /// Expression (includes call and invoking)
-    Expr(Box<Expr>),
+    Expr(Box<Spanned<Expr>>),
     /// A Block statement
-    Block(Box<Block>),
+    Block(Box<Spanned<Block>>),
     /// A Loop statement
     Loop(Box<Loop>),
+    /// A While statement
+    While(Box<While>),
+    /// A For statement
+    For(Box<For>),
+}
+
+/// Something that can be assigned to, on the left side of `=`
+#[derive(Debug, Clone)]
+pub enum Place {
+    /// A variable, as in `x`
+    Var(String),
+    /// An element of an array held in a place, as in `arr[i]` or `m[i][j]`
+    Index(Box<Spanned<Place>>, Box<Spanned<Expr>>),
 }
 
 /// A Statement Block. Simply a list of sequential statements
 #[derive(Debug, Clone)]
-pub struct Block(pub Vec<Statement>);
+pub struct Block(pub Vec<Spanned<Statement>>);
+
+/// A Loop statement. Repeats its block until a `break` is found.
+#[derive(Debug, Clone)]
+pub struct Loop {
+    /// Label used by `break` and `continue` to refer to this loop, without the leading `'`
+    pub label: Option<String>,
+    /// Block to be repeated
+    pub body: Box<Spanned<Block>>,
+}
+
+/// A While statement. Repeats its block as long as the condition evaluates to `true`.
+#[derive(Debug, Clone)]
+pub struct While {
+    /// Label used by `break` and `continue` to refer to this loop, without the leading `'`
+    pub label: Option<String>,
+    /// Condition checked before every iteration. Must evaluate to a [Literal::Bool]
+    pub cond: Box<Spanned<Expr>>,
+    /// Block to be repeated
+    pub body: Box<Spanned<Block>>,
+}
 
-/// A Loop statement. Only contains a block.
+/// A For statement. Repeats its block once for every element of an iterable value.
 #[derive(Debug, Clone)]
-pub struct Loop(pub Box<Block>);
+pub struct For {
+    /// Label used by `break` and `continue` to refer to this loop, without the leading `'`
+    pub label: Option<String>,
+    /// Name bound to the current element on every iteration
+    pub var: String,
+    /// Value to iterate over. Must evaluate to a [Literal::Range] or a [Literal::Array]
+    pub iter: Box<Spanned<Expr>>,
+    /// Block to be repeated
+    pub body: Box<Spanned<Block>>,
+}
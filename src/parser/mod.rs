@@ -2,13 +2,16 @@
 
 use chumsky::{prelude::*, text::Character};
 
+use crate::check;
+use crate::error::Span;
+
 pub mod ast;
 use ast::*;
 
 // Terminals (not made from other types) {
 
 /// Parses a single inline or block comment
-pub fn comment_parser() -> impl Parser<char, (), Error = Simple<char>> + Clone + Copy {
+pub fn comment_parser() -> impl Parser<char, (), Error = Simple<char>> + Copy {
     // Parse "//"
     let inline_comment = just("//")
         // Ignore input until newline
@@ -29,65 +32,480 @@ pub fn comment_parser() -> impl Parser<char, (), Error = Simple<char>> + Clone +
 // LINK ../unittest/mod.rs#unicode-tests
 /// Parses identifiers (variable/function names), defined as per [`chumsky::text::ident()`]
 pub fn identifier_parser(
-) -> impl Parser<char, <char as Character>::Collection, Error = Simple<char>> + Copy + Clone {
+) -> impl Parser<char, <char as Character>::Collection, Error = Simple<char>> + Copy {
     text::ident().padded()
 }
 
-/// Parses an integer number of radix 10
-// TODO for radix != 10, preceded by 0b, 0t, 0x
-pub fn integer_parser() -> impl Parser<char, Expr, Error = Simple<char>> + Copy + Clone {
-    // Parse for base 10
-    text::int(10)
-        .map(|s: String| {
-            Expr::Literal(Literal::Num(Number::Integer(s.parse().unwrap())))
-            // Expr::Literal(Literal::Num({
-            //     let int = s.parse::<usize>();
-            //     if let Ok(int) = int {
-            //         Number::UInteger(int)
-            //     } else {
-            //         Number::Integer(s.parse::<isize>().expect("Error parsing integer literal"))
-            //     }
-            // }))
-        })
-        .padded()
+/// A number literal as written in source, split in its parts. Whether it is valid is only checked when its value is taken
+struct NumberLiteral {
+    /// Radix of the digits, from the `0x`, `0o` or `0b` prefix, if any
+    radix: u32,
+    /// Digits of the integer part, possibly separated by `_`
+    int: String,
+    /// Digits after the `.`, if there is one. May be empty (e.g.: "2." is a float)
+    fraction: Option<String>,
+    /// Sign and digits of the exponent, as in `e-3`
+    exponent: Option<(Option<char>, String)>,
+    /// Type suffix, as in `u8` or `f32`
+    suffix: Option<String>,
+}
+
+impl NumberLiteral {
+    /// Whether the literal is written as a float: with a `.`, an exponent or a float suffix
+    fn is_float(&self) -> bool {
+        self.fraction.is_some()
+            || self.exponent.is_some()
+            || self
+                .suffix
+                .as_deref()
+                .and_then(NumberType::from_name)
+                .is_some_and(NumberType::is_float)
+    }
+
+    /// Whether the literal has the suffix of an unsigned integer type, as in `1u8`
+    fn is_unsigned(&self) -> bool {
+        self.suffix
+            .as_deref()
+            .and_then(NumberType::from_name)
+            .is_some_and(NumberType::is_unsigned)
+    }
+
+    /// Value of the literal, or what is wrong with it. A `negative` literal has a `-` before it, which is part of its value
+    fn value(self, negative: bool) -> Result<Number, String> {
+        let ty = match &self.suffix {
+            Some(suffix) => NumberType::from_name(suffix)
+                .ok_or_else(|| format!("invalid suffix `{}` for number literal", suffix))?,
+            None if self.is_float() => NumberType::Float,
+            None => NumberType::Integer,
+        };
+        let int = self.int.replace('_', "");
+        if int.is_empty() {
+            return Err("no valid digits found for number".to_string());
+        }
+        if let Some(digit) = int.chars().find(|c| !c.is_digit(self.radix)) {
+            return Err(format!(
+                "invalid digit `{}` for a base {} literal",
+                digit, self.radix
+            ));
+        }
+
+        if !ty.is_float() {
+            if self.fraction.is_some() || self.exponent.is_some() {
+                return Err(format!("invalid suffix `{}` for float literal", ty.name()));
+            }
+            // Every integer literal fits in the widest unsigned type, or it fits in none at all.
+            // Negated, it fits in the widest signed type
+            return u64::from_str_radix(&int, self.radix)
+                .ok()
+                .and_then(|int| match negative {
+                    false => Some(Number::U64(int)),
+                    true => i64::try_from(-(int as i128)).ok().map(Number::I64),
+                })
+                .and_then(|int| int.try_cast(ty))
+                .ok_or_else(|| format!("integer literal is too large for `{}`", ty.name()));
+        }
+
+        if self.radix != 10 {
+            return Err("float literals must be in base 10".to_string());
+        }
+        let fraction = match self.fraction {
+            // The number after the dot can be omitted, but then nothing else can follow
+            Some(fraction)
+                if fraction.is_empty() && (self.exponent.is_some() || self.suffix.is_some()) =>
+            {
+                return Err("expected digits after `.` in float literal".to_string())
+            }
+            fraction => fraction.unwrap_or_default(),
+        };
+        let exponent = match self.exponent {
+            Some((_, digits)) if digits.replace('_', "").is_empty() => {
+                return Err("expected at least one digit in exponent".to_string())
+            }
+            Some((sign, digits)) => format!("e{}{}", sign.unwrap_or('+'), digits),
+            None => String::new(),
+        };
+        let sign = if negative { "-" } else { "" };
+        let literal = format!("{}{}.{}0{}", sign, int, fraction, exponent).replace('_', "");
+        match ty {
+            NumberType::F32 => literal
+                .parse()
+                .ok()
+                .filter(|x: &f32| x.is_finite())
+                .map(Number::F32),
+            ty => literal
+                .parse()
+                .ok()
+                .filter(|x: &f64| x.is_finite())
+                .map(|x| Number::Float(x).cast(ty)),
+        }
+        .ok_or_else(|| format!("float literal is out of range for `{}`", ty.name()))
+    }
+}
+
+/// Parses the integer digits of a number literal, along with their radix.
+/// Radix is 10, or 16, 8 or 2 when preceded by `0x`, `0o` or `0b`, and digits may be separated by `_`, as in `0b1111_0000`
+fn digits_parser() -> impl Parser<char, (u32, String), Error = Simple<char>> + Copy {
+    // Letters are taken as digits for any radix, so `0b12` is an invalid digit instead of a `0b1` followed by garbage
+    let prefixed = just("0x")
+        .to(16)
+        .or(just("0o").to(8))
+        .or(just("0b").to(2))
+        .then(
+            filter(|c: &char| c.is_ascii_hexdigit() || *c == '_')
+                .repeated()
+                .collect::<String>(),
+        );
+    // A decimal literal must start with a digit, or it would be an identifier. Like `text::int()`, leading zeros are not allowed
+    let decimal = filter(|c: &char| ('1'..='9').contains(c))
+        .chain(filter(|c: &char| c.is_ascii_digit() || *c == '_').repeated())
+        .or(just('0').chain(just('_').repeated()))
+        .collect::<String>()
+        .map(|digits| (10, digits));
+    prefixed.or(decimal)
+}
+
+/// Parses the parts of any number literal, valid or not, so invalid ones are reported as such instead of as unexpected characters
+fn number_literal_parser() -> impl Parser<char, NumberLiteral, Error = Simple<char>> + Copy {
+    let digits = filter(|c: &char| c.is_ascii_digit() || *c == '_')
+        .repeated()
+        .collect::<String>();
+    let exponent = just('e')
+        .or(just('E'))
+        .ignore_then(just('+').or(just('-')).or_not())
+        .then(digits);
+
+    digits_parser()
+        .then(just('.').ignore_then(digits).or_not())
+        .then(exponent.or_not())
+        .then(text::ident().or_not())
+        .map(
+            |((((radix, int), fraction), exponent), suffix)| NumberLiteral {
+                radix,
+                int,
+                fraction,
+                exponent,
+                suffix,
+            },
+        )
+}
+
+/// Parses an integer number, with an optional type suffix, as in `10u8` or `0xff_u8`. Without a suffix, it is a [Number::Integer]
+pub fn integer_parser() -> impl Parser<char, Expr, Error = Simple<char>> + Copy {
+    number_literal_parser().try_map(|literal, span| {
+        if literal.is_float() {
+            return Err(Simple::custom(span, "expected an integer literal"));
+        }
+        literal
+            .value(false)
+            .map(|number| Expr::Literal(Literal::Num(number)))
+            .map_err(|message| Simple::custom(span, message))
+    })
 }
 
-// TODO parse scientific notation
 // LINK ../unittest/mod.rs#scientific-notation-test
-/// Parses a floating-point number
-pub fn float_parser() -> impl Parser<char, Expr, Error = Simple<char>> + Copy + Clone {
-    // Try to match a integer, then a dot, then another series of digits
-    text::int::<_, Simple<char>>(10)
-        .then_ignore(just('.'))
-        .then(text::digits(10).or_not())
-        .map(|s: (String, Option<String>)| {
-            Expr::Literal(Literal::Num(Number::Float(
-                // The number after the dot can be omitted (e.g.: "2." is a float)
-                format!("{}.{}", s.0, s.1.unwrap_or_else(|| "".to_string()))
-                    .parse()
-                    .unwrap(),
-            )))
+/// Parses a floating-point number, as in `2.5`, `1e10`, `2.5E-3` or `1.0f32`. Without a suffix, it is a [Number::Float]
+pub fn float_parser() -> impl Parser<char, Expr, Error = Simple<char>> + Copy {
+    number_literal_parser().try_map(|literal, span| {
+        if !literal.is_float() {
+            return Err(Simple::custom(span, "expected a float literal"));
+        }
+        literal
+            .value(false)
+            .map(|number| Expr::Literal(Literal::Num(number)))
+            .map_err(|message| Simple::custom(span, message))
+    })
+}
+
+/// Parses an associated constant of a numeric type, as in `f64::NAN` or `u8::MAX`
+pub fn number_constant_parser() -> impl Parser<char, Expr, Error = Simple<char>> + Copy {
+    text::ident()
+        .then_ignore(just("::"))
+        .then(text::ident())
+        // Failing past the `::`, so the error wins over taking `f64` as a variable
+        .try_map(|(ty, name): (String, String), span: Span| {
+            let ty = NumberType::from_name(&ty).ok_or_else(|| {
+                Simple::custom(span.clone(), format!("no numeric type named `{}`", ty))
+            })?;
+            Number::constant(ty, &name)
+                .map(|number| Expr::Literal(Literal::Num(number)))
+                .ok_or_else(|| {
+                    Simple::custom(
+                        span,
+                        format!("no constant named `{}` in `{}`", name, ty.name()),
+                    )
+                })
         })
-        .padded()
+}
+
+/// Parses the name of a numeric type, as used in `as` casts
+pub fn number_type_parser() -> impl Parser<char, NumberType, Error = Simple<char>> + Copy {
+    text::ident().try_map(|name: String, span| {
+        NumberType::from_name(&name).ok_or_else(|| {
+            Simple::custom(
+                span,
+                format!("cannot cast to `{}`, only to numeric types", name),
+            )
+        })
+    })
+}
+
+/// Parses the parts of any number literal.
+/// An integer followed by two dots is the start of a range (e.g.: "0..10"), not the float "0."
+fn number_parts_parser() -> impl Parser<char, NumberLiteral, Error = Simple<char>> + Copy {
+    let range_start = digits_parser()
+        .then(text::ident().or_not())
+        .then_ignore(just("..").rewind())
+        .map(|((radix, int), suffix)| NumberLiteral {
+            radix,
+            int,
+            fraction: None,
+            exponent: None,
+            suffix,
+        });
+    range_start.or(number_literal_parser())
 }
 
 /// Any number. Ints or floats
-pub fn number_parser() -> impl Parser<char, Expr, Error = Simple<char>> + Copy + Clone {
-    float_parser().or(integer_parser())
+pub fn number_parser() -> impl Parser<char, Expr, Error = Simple<char>> + Copy {
+    number_parts_parser().try_map(|literal, span| {
+        literal
+            .value(false)
+            .map(|number| Expr::Literal(Literal::Num(number)))
+            .map_err(|message| Simple::custom(span, message))
+    })
+}
+
+/// A number with a `-` before it, as in `-128i8`. The `-` is part of the literal, so its range is checked with it,
+/// as in Rust. Unsigned literals are rejected, leaving the `-` to be applied to them and fail as usual
+pub fn negative_number_parser() -> impl Parser<char, Expr, Error = Simple<char>> + Copy {
+    just('-')
+        .padded()
+        .ignore_then(number_parts_parser())
+        .try_map(|literal, span| {
+            if literal.is_unsigned() {
+                return Err(Simple::custom(span, "unsigned literals can't be negated"));
+            }
+            literal
+                .value(true)
+                .map(|number| Expr::Literal(Literal::Num(number)))
+                .map_err(|message| Simple::custom(span, message))
+        })
 }
 
 /// True of false. Rejects on anything else
-pub fn boolean_parser() -> impl Parser<char, Expr, Error = Simple<char>> + Copy + Clone {
+pub fn boolean_parser() -> impl Parser<char, Expr, Error = Simple<char>> + Copy {
     just("true")
         .or(just("false"))
         .map(|s| Expr::Literal(Literal::Bool(s.parse().unwrap())))
 }
 
-/// Parses the string type. Does not support escaping
-pub fn string_parser() -> impl Parser<char, Expr, Error = Simple<char>> + Copy + Clone {
-    just('"')
-        .ignore_then(take_until(just('"').ignored()))
-        .map(|(s, _)| Expr::Literal(Literal::Str(s.iter().collect())))
+/// Parses an escape sequence, as in Rust: `\n`, `\r`, `\t`, `\0`, `\\`, `\"`, `\'`, `\x7F` and `\u{1F600}`.
+/// In `bytes` literals, `\x` goes up to `\xFF` and `\u` is not allowed, so every escape is a single byte
+fn escape_parser(bytes: bool) -> impl Parser<char, char, Error = Simple<char>> + Clone {
+    let hex_digit = filter(|c: &char| c.is_ascii_hexdigit());
+    let ascii = just('n')
+        .to('\n')
+        .or(just('r').to('\r'))
+        .or(just('t').to('\t'))
+        .or(just('0').to('\0'))
+        .or(just('\\'))
+        .or(just('"'))
+        .or(just('\''));
+    let hex = just("\\x")
+        .ignore_then(hex_digit.repeated().exactly(2).collect::<String>())
+        .try_map(move |digits, span| match u8::from_str_radix(&digits, 16) {
+            Ok(byte) if bytes || byte.is_ascii() => Ok(char::from(byte)),
+            _ => Err(Simple::custom(
+                span,
+                "out of range hex escape, it must be at most `\\x7F`",
+            )),
+        });
+    let unicode = just("\\u")
+        .ignore_then(
+            hex_digit
+                .or(just('_'))
+                .repeated()
+                .at_least(1)
+                .collect::<String>()
+                .delimited_by(just('{'), just('}')),
+        )
+        .try_map(move |digits, span| {
+            if bytes {
+                return Err(Simple::custom(
+                    span,
+                    "unicode escapes are not allowed in byte literals",
+                ));
+            }
+            let digits = digits.replace('_', "");
+            if digits.is_empty() || digits.len() > 6 {
+                return Err(Simple::custom(
+                    span,
+                    "unicode escapes must have from 1 to 6 hex digits",
+                ));
+            }
+            u32::from_str_radix(&digits, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or_else(|| Simple::custom(span, "invalid unicode character escape"))
+        });
+    just('\\').ignore_then(ascii).or(hex).or(unicode)
+}
+
+/// Parses a character between `quote`s, escaped or not. Characters that would end the literal have to be escaped,
+/// and so do newlines and tabs in `char`s. `bytes` literals only take ASCII characters
+fn quoted_char_parser(
+    quote: char,
+    bytes: bool,
+) -> impl Parser<char, char, Error = Simple<char>> + Clone {
+    filter(move |c: &char| {
+        *c != quote && *c != '\\' && (quote == '"' || !matches!(c, '\n' | '\r' | '\t'))
+    })
+    .try_map(move |c, span| {
+        if bytes && !c.is_ascii() {
+            Err(Simple::custom(span, "non-ASCII character in byte literal"))
+        } else {
+            Ok(c)
+        }
+    })
+    .or(escape_parser(bytes))
+}
+
+/// Parses the contents of a quoted string, up to the closing `"`. A `\` at the end of a line skips the line break
+/// and the whitespace that follows it, as in Rust
+fn quoted_string_parser(bytes: bool) -> impl Parser<char, String, Error = Simple<char>> + Clone {
+    let line_continuation = just('\\').then(text::newline()).then(text::whitespace());
+    quoted_char_parser('"', bytes)
+        .map(Some)
+        .or(line_continuation.to(None))
+        .repeated()
+        .delimited_by(just('"'), just('"'))
+        .map(|chars| chars.into_iter().flatten().collect())
+}
+
+/// Parses a raw string after its `r`, as in `r"C:\dir"` or `r#"say "hi""#`. Nothing is escaped inside it,
+/// and it ends at the first `"` followed by as many `#`s as it started with
+fn raw_string_parser() -> impl Parser<char, String, Error = Simple<char>> + Clone {
+    just('#')
+        .repeated()
+        .then_ignore(just('"'))
+        .then_with(|hashes| {
+            take_until(just('"').then(just('#').repeated().exactly(hashes.len())))
+                .map(|(chars, _)| chars.into_iter().collect())
+        })
+}
+
+/// Parses strings, as in `"a\tb"` or `r#"a"b"#`, and byte strings, as in `b"a\xFF"` or `br"a"`.
+/// Byte strings are arrays of `u8`s, like the `[u8; N]` they are in Rust
+pub fn string_parser() -> impl Parser<char, Expr, Error = Simple<char>> + Clone {
+    let string = quoted_string_parser(false)
+        .or(just('r').ignore_then(raw_string_parser()))
+        .map(Literal::Str);
+    let raw_bytes = just('r')
+        .ignore_then(raw_string_parser())
+        .try_map(|string, span| match string.is_ascii() {
+            true => Ok(string),
+            false => Err(Simple::custom(span, "non-ASCII character in byte literal")),
+        });
+    // Every char left in a byte string is at most `\xFF`, so it is a single byte
+    let bytes = just('b')
+        .ignore_then(quoted_string_parser(true).or(raw_bytes))
+        .map(|string| {
+            let bytes = string.chars().map(|c| Literal::Num(Number::U8(c as u8)));
+            Literal::Array(bytes.collect())
+        });
+    string.or(bytes).map(Expr::Literal)
+}
+
+/// Parses `char`s, as in `'a'` or `'\n'`, and byte literals, as in `b'a'`, which are `u8`s.
+/// The closing `'` tells them apart from labels, as in `'a: loop {}`
+pub fn char_parser() -> impl Parser<char, Expr, Error = Simple<char>> + Clone {
+    let quoted = |bytes| quoted_char_parser('\'', bytes).delimited_by(just('\''), just('\''));
+    quoted(false)
+        .map(Literal::Char)
+        .or(just('b')
+            .ignore_then(quoted(true))
+            .map(|c| Literal::Num(Number::U8(c as u8))))
+        .map(Expr::Literal)
+}
+
+/// Finds the first bracket or prefix operator in `src` that nests deeper than `limit`, counting the `(`, `[` and `{`
+/// not closed yet, and the `-`, `!` and closure heads, as in `|x|`, in a row before it.
+/// Comments, strings and `char`s are skipped. Parsing is recursive, so this is checked on the source before it is parsed
+pub fn nesting_over(src: &str, limit: usize) -> Option<Span> {
+    let chars: Vec<char> = src.chars().collect();
+    let at = |i: usize| chars.get(i).copied();
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let mut brackets = 0usize;
+    let mut prefix = 0usize;
+    // Last character that isn't whitespace, to tell closures from `|` and `||`, which only come after an operand
+    let mut prev = None;
+    let mut i = 0;
+    while let Some(c) = at(i) {
+        let operand = prev.is_none_or(|prev| "([{,;=|!-+*/%<>&^".contains(prev));
+        // Where the next token starts
+        let next = match (c, at(i + 1)) {
+            ('/', Some('/')) => (i..chars.len())
+                .find(|&j| chars[j] == '\n')
+                .unwrap_or(chars.len()),
+            ('/', Some('*')) => (i + 2..chars.len())
+                .find(|&j| chars[j] == '*' && at(j + 1) == Some('/'))
+                .map_or(chars.len(), |j| j + 2),
+            // Raw strings, as in `r#"a"b"#` and `br"a"`, end at a `"` followed by as many `#` as they start with
+            ('r', _) | ('b', Some('r')) if i == 0 || !is_ident(chars[i - 1]) => {
+                let start = i + if c == 'b' { 2 } else { 1 };
+                let hashes = (start..chars.len())
+                    .take_while(|&j| chars[j] == '#')
+                    .count();
+                match at(start + hashes) {
+                    Some('"') => (start + hashes + 1..chars.len())
+                        .find(|&j| chars[j] == '"' && (1..=hashes).all(|k| at(j + k) == Some('#')))
+                        .map_or(chars.len(), |j| j + hashes + 1),
+                    _ => i + 1,
+                }
+            }
+            ('"', _) => {
+                let mut j = i + 1;
+                while let Some(c) = at(j) {
+                    j += if c == '\\' { 2 } else { 1 };
+                    if c == '"' {
+                        break;
+                    }
+                }
+                j
+            }
+            // A `'` that isn't closed right after the `char` is a label, as in `'outer`
+            ('\'', Some('\\')) => (i + 3..chars.len())
+                .find(|&j| chars[j] == '\'')
+                .map_or(chars.len(), |j| j + 1),
+            ('\'', Some(_)) if at(i + 2) == Some('\'') => i + 3,
+            ('|', Some('|')) => i + 2,
+            ('|', _) if operand => (i + 1..chars.len())
+                .find(|&j| chars[j] == '|')
+                .map_or(chars.len(), |j| j + 1),
+            _ => i + 1,
+        };
+        match c {
+            '(' | '[' | '{' => brackets += 1,
+            ')' | ']' | '}' => brackets = brackets.saturating_sub(1),
+            _ => (),
+        }
+        match c {
+            // Not `!=`, `-=` or `->`
+            '-' | '!' if !matches!(at(i + 1), Some('=' | '>')) => prefix += 1,
+            '|' if operand => prefix += 1,
+            '/' if matches!(at(i + 1), Some('/' | '*')) => (),
+            c if c.is_whitespace() => (),
+            _ => prefix = 0,
+        }
+        if brackets + prefix > limit {
+            return Some(i..next);
+        }
+        if !(c.is_whitespace() || c == '/' && matches!(at(i + 1), Some('/' | '*'))) {
+            prev = Some(chars[next - 1]);
+        }
+        i = next;
+    }
+    None
 }
 
 // }
@@ -95,14 +513,17 @@ pub fn string_parser() -> impl Parser<char, Expr, Error = Simple<char>> + Copy +
 
 /// Parses an variable assignment
 pub fn assignment_parser() -> impl Parser<char, Statement, Error = Simple<char>> + Clone {
-    identifier_parser()
-        .then_ignore(just('='))
-        .then(expr_parser())
-        .then_ignore(just(";"))
-        .map(|(lvalue, rvalue)| Statement::Assign {
-            lvalue,
-            rvalue: Box::new(rvalue),
-        })
+    let (statement, _, _, _, _, _, _, assign) = statement_block_item_loop_parser();
+    // Parsers built inside the statement parser only hold weak references to it, so it is moved in to be kept alive
+    assign.map(move |assign| {
+        let _statement = &statement;
+        assign
+    })
+}
+
+/// Parses a loop label, as in `'outer`
+pub fn label_parser() -> impl Parser<char, String, Error = Simple<char>> + Copy {
+    just('\'').ignore_then(text::ident()).padded()
 }
 
 /// Statement-block-item parser. It parses all three, and are nested together because of the recursive nature of them (statement may be a block, a block is made of statements and items, with are made of functions which includes blocks etc.)
@@ -111,7 +532,7 @@ pub fn assignment_parser() -> impl Parser<char, Statement, Error = Simple<char>>
 ///
 /// A statement is a component of a block, which is in turn a component of an outer expression or function
 /// Differences from Rust:
-/// - Doesn't support macro invocation
+/// - Only supports the formatting macros, `print!`, `println!`, `eprint!`, `eprintln!` and `format!`
 /// - Has to end with `;` in any circunstance. It always returns evaluated value. Put an additional `;` if you want to return `;`'s value, which is ``null'' (`()`)
 ///
 /// A block is a list of a statement
@@ -123,35 +544,44 @@ pub fn assignment_parser() -> impl Parser<char, Statement, Error = Simple<char>>
 /// A function consists of a block, along with a name and a set of parameters. Other than a name, all these are optional. Functions are declared with the keyword fn. Functions may declare a set of input variables as parameters, through which the caller passes arguments into the function, and the output type of the value the function will return to its caller on completion
 /// Differences from Rust:
 /// - Only supports function declarations
-/// - No support for nested functions
+/// - Functions declared inside a block can be called from anywhere in that block, but can't see its variables, as in Rust
+///
+/// A `return` statement exits the function it is in, even from inside loops and nested blocks.
 ///
 /// A Loop is a controle structure to repeat determined Statements, or, more precisely, a Block.
-/// A `break` statement may be placed to stop looping.
+/// A `break` statement may be placed to stop looping. `break value` makes `value` the result of the Loop, so a Loop can also be used as an expression.
+///
+/// A While is a Loop with a condition, checked before every iteration. `break` works the same way it does inside a Loop.
+///
+/// A For is a Loop over the elements of a range or an array, binding each element to a name in a fresh scope.
+/// Inside any kind of Loop, `continue` skips the rest of the current iteration.
+/// Loops may be labeled (`'outer: loop {}`), and `break 'outer` or `continue 'outer` then refer to that Loop instead of the innermost one.
+///
+/// Expressions are also built here, since a Loop is an expression made of statements.
+#[allow(clippy::type_complexity)]
 pub fn statement_block_item_loop_parser() -> (
-    impl Parser<char, Statement, Error = Simple<char>> + Clone,
-    impl Parser<char, Block, Error = Simple<char>> + Clone,
+    impl Parser<char, Spanned<Statement>, Error = Simple<char>> + Clone,
+    impl Parser<char, Spanned<Block>, Error = Simple<char>> + Clone,
     impl Parser<char, Item, Error = Simple<char>> + Clone,
     impl Parser<char, Loop, Error = Simple<char>> + Clone,
+    impl Parser<char, While, Error = Simple<char>> + Clone,
+    impl Parser<char, For, Error = Simple<char>> + Clone,
+    impl Parser<char, Spanned<Expr>, Error = Simple<char>> + Clone,
+    impl Parser<char, Statement, Error = Simple<char>> + Clone,
 ) {
     let identifier = identifier_parser();
     let comment = comment_parser();
-    let expr = expr_parser();
-    let assign = assignment_parser();
-
-    let r#let = text::keyword("let")
-        .ignored()
-        .then(assign.clone())
-        .map(|(_, statement)| {
-            if let Statement::Assign { lvalue, rvalue } = statement {
-                Statement::Let { lvalue, rvalue }
-            } else {
-                unreachable!()
-            }
-        });
+    let label = label_parser();
+    // Labels are declared as `'label:` in front of the loop keyword
+    let loop_label = label.then_ignore(just(':')).padded().or_not();
 
     let mut block = None;
     let mut item = None;
     let mut r#loop = None;
+    let mut r#while = None;
+    let mut r#for = None;
+    let mut expression = None;
+    let mut assignment = None;
     let statement = recursive(|statement| {
         let block_content = statement
             .padded_by(comment.padded().repeated())
@@ -159,20 +589,88 @@ pub fn statement_block_item_loop_parser() -> (
             .repeated()
             .padded()
             .map(Block);
-        let block_inner = block_content.clone().delimited_by(just("{"), just("}"));
+        let block_inner = block_content
+            .clone()
+            .delimited_by(just("{"), just("}"))
+            .map_with_span(Spanned::new);
         block = Some(block_inner.clone());
 
+        let loop_inner = loop_label
+            .then_ignore(text::keyword("loop").padded())
+            .then(block_inner.clone())
+            .map(|(label, body)| Loop {
+                label,
+                body: Box::new(body),
+            });
+        r#loop = Some(loop_inner.clone());
+
+        let expr = expr_parser_inner(loop_inner.clone(), block_inner.clone());
+        expression = Some(expr.clone());
+
+        // Three-character operators go first, so `**=` isn't taken as `*` followed by `*=`
+        let compound_op = just("**")
+            .to(Expr::Pow as BinaryOp)
+            .or(just("<<").to(Expr::Shl as BinaryOp))
+            .or(just(">>").to(Expr::Shr as BinaryOp))
+            .or(just('+').to(Expr::Add as BinaryOp))
+            .or(just('-').to(Expr::Sub as BinaryOp))
+            .or(just('*').to(Expr::Mul as BinaryOp))
+            .or(just('/').to(Expr::Div as BinaryOp))
+            .or(just('%').to(Expr::Rem as BinaryOp))
+            .or(just('&').to(Expr::BitAnd as BinaryOp))
+            .or(just('|').to(Expr::BitOr as BinaryOp))
+            .or(just('^').to(Expr::BitXor as BinaryOp));
+
+        let place = text::ident()
+            .map_with_span(|name, span| Spanned::new(Place::Var(name), span))
+            .then(
+                text::whitespace()
+                    .ignore_then(expr.clone().delimited_by(just('['), just(']')))
+                    .map_with_span(|index, span: Span| (index, span))
+                    .repeated(),
+            )
+            .foldl(|place, (index, index_span)| {
+                let span = place.span.start..index_span.end;
+                Spanned::new(Place::Index(Box::new(place), Box::new(index)), span)
+            });
+
+        let assign = place
+            .padded()
+            .then(compound_op.or_not())
+            .then_ignore(just('='))
+            .then(expr.clone())
+            .then_ignore(just(";"))
+            .map(|((lvalue, op), rvalue)| Statement::Assign {
+                lvalue,
+                op,
+                rvalue: Box::new(rvalue),
+            });
+        assignment = Some(assign.clone());
+
+        let r#let = text::keyword("let")
+            .ignore_then(text::keyword("mut").padded().or_not())
+            .then(identifier)
+            .then_ignore(just('='))
+            .then(expr.clone())
+            .then_ignore(just(";"))
+            .map(|((mutable, lvalue), rvalue)| Statement::Let {
+                mutable: mutable.is_some(),
+                lvalue,
+                rvalue: Box::new(rvalue),
+            });
+
         let function = text::keyword("fn")
             .ignore_then(identifier)
             .then_ignore(just("("))
             .then(identifier.then_ignore(just(',').or_not()).repeated())
-            .then_ignore(just(")"))
-            .then(block_inner.clone().padded())
+            .then_ignore(just(")").padded())
+            .then(block_inner.clone())
             .map(|((name, args), body)| {
                 Item::Function(Function {
                     name,
                     args,
                     body: Box::new(body),
+                    captures: Default::default(),
                 })
             });
         let item_inner = function;
@@ -197,128 +695,735 @@ pub fn statement_block_item_loop_parser() -> (
                 }
             });
 
-        let loop_inner = text::keyword("loop")
-            .padded()
-            .ignore_then(block_inner.clone())
-            .map(|s| Loop(Box::new(s)));
-        r#loop = Some(loop_inner.clone());
+        let while_inner = loop_label
+            .then_ignore(text::keyword("while").padded())
+            .then(expr.clone())
+            .then(block_inner.clone())
+            .map(|((label, cond), body)| While {
+                label,
+                cond: Box::new(cond),
+                body: Box::new(body),
+            });
+        r#while = Some(while_inner.clone());
+
+        let for_inner = loop_label
+            .then_ignore(text::keyword("for").padded())
+            .then(identifier)
+            .then_ignore(text::keyword("in").padded())
+            .then(expr.clone())
+            .then(block_inner.clone())
+            .map(|(((label, var), iter), body)| For {
+                label,
+                var,
+                iter: Box::new(iter),
+                body: Box::new(body),
+            });
+        r#for = Some(for_inner.clone());
 
         r#let
             .or(assign)
-            .or(just("break")
-                .ignore_then(just(";").or_not())
-                .ignored()
-                .map(|_| Statement::Break))
+            .or(text::keyword("break")
+                .ignore_then(label.or_not())
+                .then(expr.clone().or_not())
+                .then_ignore(just(";").or_not())
+                .map(|(label, value)| Statement::Break {
+                    label,
+                    value: value.map(Box::new),
+                }))
+            .or(text::keyword("return")
+                .ignore_then(expr.clone().or_not())
+                .then_ignore(just(";").or_not())
+                .map(|s| Statement::Return(s.map(Box::new))))
+            .or(text::keyword("continue")
+                .ignore_then(label.or_not())
+                .then_ignore(just(";").or_not())
+                .map(|label| Statement::Continue { label }))
             .or(expr
                 .map(|s| Statement::Expr(Box::new(s)))
                 .then_ignore(just(";")))
             .or(conditional)
-            .or(just("break").ignored().to(Statement::Break))
             .or(item_inner.map(|s| Statement::Item(Box::new(s))))
             .or(block_inner.map(|s| Statement::Block(Box::new(s))))
             .or(loop_inner.map(|s| Statement::Loop(Box::new(s))))
+            .or(while_inner.map(|s| Statement::While(Box::new(s))))
+            .or(for_inner.map(|s| Statement::For(Box::new(s))))
             .or(just(";").map(|_| Statement::Null))
+            .map_with_span(Spanned::new)
     });
 
-    (statement, block.unwrap(), item.unwrap(), r#loop.unwrap())
+    (
+        statement,
+        block.unwrap(),
+        item.unwrap(),
+        r#loop.unwrap(),
+        r#while.unwrap(),
+        r#for.unwrap(),
+        expression.unwrap(),
+        assignment.unwrap(),
+    )
 }
 
 /// Parses expressions, made of `atom`s
-pub fn expr_parser() -> impl Parser<char, Expr, Error = Simple<char>> + Clone {
-    let identifier = identifier_parser();
+pub fn expr_parser() -> impl Parser<char, Spanned<Expr>, Error = Simple<char>> + Clone {
+    let (statement, _, _, _, _, _, expr, _) = statement_block_item_loop_parser();
+    // Parsers built inside the statement parser only hold weak references to it, so it is moved in to be kept alive
+    expr.map(move |expr| {
+        let _statement = &statement;
+        expr
+    })
+}
+
+/// Operators with one operand, as built by [expr_parser()]
+type UnaryOp = fn(Box<Spanned<Expr>>) -> Expr;
+
+/// Folds a binary operation into a single expression, spanning from the start of `lhs` to the end of `rhs`
+fn binary_op(lhs: Spanned<Expr>, (op, rhs): (BinaryOp, Spanned<Expr>)) -> Spanned<Expr> {
+    let span = lhs.span.start..rhs.span.end;
+    Spanned::new(op(Box::new(lhs), Box::new(rhs)), span)
+}
+
+/// Reference to an argument of a formatting macro, as written in its format string
+enum ArgRef {
+    /// `{}`, the positional argument after the last one taken this way
+    Next,
+    /// `{0}`, the positional argument at an index
+    Index(usize),
+    /// `{name}`, a named argument, or a variable if there's no argument with that name
+    Name(String),
+}
+
+/// Arguments of a formatting macro, as seen while reading its format string
+struct FormatArgs<'a> {
+    /// Number of positional arguments
+    positional: usize,
+    /// Names of the named arguments, which come after the positional ones
+    names: &'a [String],
+    /// Names of the variables used in the format string, which come after the named arguments
+    captures: Vec<String>,
+    /// Whether each positional and named argument is used
+    used: Vec<bool>,
+    /// Index of the positional argument taken by the next `{}`
+    next: usize,
+}
+
+impl FormatArgs<'_> {
+    /// Index in the macro arguments of the argument `arg` refers to
+    fn resolve(&mut self, arg: ArgRef) -> Result<usize, String> {
+        let index = match arg {
+            ArgRef::Next => {
+                self.next += 1;
+                self.next - 1
+            }
+            ArgRef::Index(index) => index,
+            ArgRef::Name(name) => {
+                if let Some(i) = self.names.iter().position(|n| *n == name) {
+                    self.used[self.positional + i] = true;
+                    return Ok(self.positional + i);
+                }
+                let i = match self.captures.iter().position(|n| *n == name) {
+                    Some(i) => i,
+                    None => {
+                        self.captures.push(name);
+                        self.captures.len() - 1
+                    }
+                };
+                return Ok(self.positional + self.names.len() + i);
+            }
+        };
+        if index >= self.positional {
+            return Err(format!(
+                "invalid reference to positional argument {} ({})",
+                index,
+                match self.positional {
+                    0 => "no arguments were given".to_string(),
+                    1 => "there is 1 argument".to_string(),
+                    n => format!("there are {} arguments", n),
+                }
+            ));
+        }
+        self.used[index] = true;
+        Ok(index)
+    }
+}
+
+/// Reads an argument reference from the start of `s`: an index, a name, or nothing at all for [ArgRef::Next]
+fn arg_ref(s: &mut &str) -> Result<ArgRef, String> {
+    let len = s
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(s.len());
+    let (arg, rest) = s.split_at(len);
+    *s = rest;
+    match arg.chars().next() {
+        None => Ok(ArgRef::Next),
+        Some('0'..='9') => arg
+            .parse()
+            .map(ArgRef::Index)
+            .map_err(|_| format!("invalid argument index `{}` in format string", arg)),
+        Some(_) => Ok(ArgRef::Name(arg.to_string())),
+    }
+}
+
+/// Reads a width or precision from the start of `s`: a number, or an argument followed by `$`, as in `1$` or `width$`
+fn count(s: &mut &str, args: &mut FormatArgs) -> Result<Option<Count>, String> {
+    let start = *s;
+    let arg = arg_ref(s)?;
+    if let Some(rest) = s.strip_prefix('$') {
+        *s = rest;
+        return match arg {
+            ArgRef::Next => Err("expected an argument before `$` in format string".to_string()),
+            arg => args.resolve(arg).map(|arg| Some(Count::Arg(arg))),
+        };
+    }
+    match arg {
+        ArgRef::Next => Ok(None),
+        ArgRef::Index(n) if n <= MAX_COUNT => Ok(Some(Count::Is(n))),
+        ArgRef::Index(n) => Err(format!(
+            "width or precision {} in format string is over the limit of {}",
+            n, MAX_COUNT
+        )),
+        // Not a count, so it is left for what comes next
+        ArgRef::Name(_) => {
+            *s = start;
+            Ok(None)
+        }
+    }
+}
+
+/// Reads the inside of a `{...}` of a format string, as in `x:>8.2?`
+fn format_arg(inner: &str, args: &mut FormatArgs) -> Result<FormatPiece, String> {
+    let (mut arg, mut spec) = match inner.split_once(':') {
+        Some((arg, spec)) => (arg, spec),
+        None => (inner, ""),
+    };
+    let arg = arg_ref(&mut arg).and_then(|r| match arg.is_empty() {
+        true => Ok(r),
+        false => Err(format!("invalid argument `{}` in format string", inner)),
+    })?;
+
+    let align = |c| match c {
+        '<' => Some(Align::Left),
+        '^' => Some(Align::Center),
+        '>' => Some(Align::Right),
+        _ => None,
+    };
+    let mut chars = spec.chars();
+    // Number of `char`s taken by the fill and the alignment
+    let (fill, align, len) = match (chars.next(), chars.next()) {
+        (Some(fill), Some(c)) if align(c).is_some() => (fill, align(c), 2),
+        (Some(c), _) if align(c).is_some() => (' ', align(c), 1),
+        _ => (' ', None, 0),
+    };
+    spec = &spec[spec.char_indices().nth(len).map_or(spec.len(), |(i, _)| i)..];
+    let sign = spec.starts_with('+');
+    spec = spec.strip_prefix('+').unwrap_or(spec);
+    // `0` followed by `$` is the index of the width, not the flag
+    let zero = spec.starts_with('0') && !spec.starts_with("0$");
+    if zero {
+        spec = &spec[1..];
+    }
+    let width = count(&mut spec, args)?;
+    let precision = match spec.strip_prefix('.') {
+        // `.*` takes the precision from the next positional argument, before the value itself
+        Some(rest) if rest.starts_with('*') => {
+            spec = &rest[1..];
+            Some(Count::Arg(args.resolve(ArgRef::Next)?))
+        }
+        Some(rest) => {
+            spec = rest;
+            Some(count(&mut spec, args)?.ok_or("expected a precision after `.` in format string")?)
+        }
+        None => None,
+    };
+    let debug = match spec {
+        "" => false,
+        "?" => true,
+        spec => return Err(format!("unknown format trait `{}`", spec)),
+    };
+
+    Ok(FormatPiece::Arg {
+        arg: args.resolve(arg)?,
+        spec: FormatSpec {
+            fill,
+            align,
+            sign,
+            zero,
+            width,
+            precision,
+            debug,
+        },
+    })
+}
+
+/// Splits the `format` string of a formatting macro into its pieces, checking every argument is used as in Rust.
+/// There are `positional` arguments before the named ones, called `names`.
+/// Returns the pieces, and the variables used by name in `format` that are not named arguments
+fn format_pieces(
+    format: &str,
+    positional: usize,
+    names: &[String],
+) -> Result<(Vec<FormatPiece>, Vec<String>), String> {
+    let mut args = FormatArgs {
+        positional,
+        names,
+        captures: Vec::new(),
+        used: vec![false; positional + names.len()],
+        next: 0,
+    };
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '}' => return Err("unmatched `}` in format string, use `}}` to write it".to_string()),
+            '{' => {
+                let mut inner = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => inner.push(c),
+                        None => {
+                            return Err(
+                                "unclosed `{` in format string, use `{{` to write it".to_string()
+                            )
+                        }
+                    }
+                }
+                if !text.is_empty() {
+                    pieces.push(FormatPiece::Text(std::mem::take(&mut text)));
+                }
+                pieces.push(format_arg(&inner, &mut args)?);
+            }
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(FormatPiece::Text(text));
+    }
+
+    match args.used.iter().position(|used| !used) {
+        Some(i) if i < positional => Err(format!("argument {} is never used", i)),
+        Some(i) => Err(format!(
+            "named argument `{}` is never used",
+            names[i - positional]
+        )),
+        None => Ok((pieces, args.captures)),
+    }
+}
+
+/// Builds a formatting macro invocation out of its `name` and its arguments, the named ones with their names.
+/// The first argument is the format string. `span` is the whole invocation
+fn format_macro(
+    name: String,
+    mut args: Vec<(Option<String>, Spanned<Expr>)>,
+    span: Span,
+) -> Result<Expr, Simple<char>> {
+    let r#macro = match name.as_str() {
+        "print" => Macro::Print,
+        "println" => Macro::Println,
+        "eprint" => Macro::Eprint,
+        "eprintln" => Macro::Eprintln,
+        "format" => Macro::Format,
+        _ => {
+            return Err(Simple::custom(
+                span,
+                format!("cannot find macro `{}`", name),
+            ))
+        }
+    };
+    let format = (!args.is_empty()).then(|| args.remove(0));
+    let format = match &format {
+        Some((
+            None,
+            Spanned {
+                node: Expr::Literal(Literal::Str(format)),
+                span,
+            },
+        )) => Spanned::new(format.clone(), span.clone()),
+        Some((_, format)) => {
+            return Err(Simple::custom(
+                format.span.clone(),
+                "format argument must be a string literal",
+            ))
+        }
+        // `println!()` prints an empty line
+        None if matches!(r#macro, Macro::Println | Macro::Eprintln) => {
+            Spanned::new(String::new(), span.clone())
+        }
+        None => {
+            return Err(Simple::custom(
+                span,
+                format!("`{}!` requires at least a format string argument", name),
+            ))
+        }
+    };
+
+    let positional = args.iter().take_while(|(name, _)| name.is_none()).count();
+    let mut names = Vec::new();
+    for (name, arg) in &args[positional..] {
+        match name {
+            None => {
+                return Err(Simple::custom(
+                    arg.span.clone(),
+                    "positional arguments cannot follow named arguments",
+                ))
+            }
+            Some(name) if names.contains(name) => {
+                return Err(Simple::custom(
+                    arg.span.clone(),
+                    format!("duplicate argument named `{}`", name),
+                ))
+            }
+            Some(name) => names.push(name.clone()),
+        }
+    }
+
+    let (pieces, captures) = format_pieces(&format.node, positional, &names)
+        .map_err(|message| Simple::custom(format.span.clone(), message))?;
+    let mut args: Vec<_> = args.into_iter().map(|(_, arg)| arg).collect();
+    // Variables used in the format string are evaluated like any other variable, from where the format string is
+    args.extend(
+        captures
+            .into_iter()
+            .map(|name| Spanned::new(Expr::Var(name), format.span.clone())),
+    );
+    Ok(Expr::Macro {
+        r#macro,
+        format: pieces,
+        args,
+    })
+}
+
+/// Operators placed after an operand, as built by [expr_parser()]
+enum Postfix {
+    /// `[index]`
+    Index(Spanned<Expr>),
+    /// `(args)`
+    Call(Vec<Spanned<Expr>>),
+    /// `.method(args)`
+    Method(Spanned<String>, Vec<Spanned<Expr>>),
+}
+
+/// Builds the expression parser. Loops and closures are also expressions, so the [Loop] and [Block] parsers built with
+/// the statements are received
+fn expr_parser_inner<'a>(
+    r#loop: impl Parser<char, Loop, Error = Simple<char>> + Clone + 'a,
+    block: impl Parser<char, Spanned<Block>, Error = Simple<char>> + Clone + 'a,
+) -> impl Parser<char, Spanned<Expr>, Error = Simple<char>> + Clone + 'a {
+    // Not padded, so the spans of the atoms don't include whitespace
+    let name = text::ident::<char, Simple<char>>();
 
     let string = string_parser();
     let number = number_parser();
     let boolean = boolean_parser();
 
     recursive(|expr| {
-        let call = identifier
+        // `||` starting an operand is a closure without arguments, not the `||` operator
+        let closure = just("||")
+            .to(Vec::new())
+            .or(identifier_parser()
+                .separated_by(just(','))
+                .allow_trailing()
+                .delimited_by(just('|'), just('|')))
+            .then(block.padded().or(expr.clone().map(|body: Spanned<Expr>| {
+                let span = body.span.clone();
+                let statement = Spanned::new(Statement::Expr(Box::new(body)), span.clone());
+                Spanned::new(Block(vec![statement]), span)
+            })))
+            .map(|(args, body)| Expr::Closure {
+                captures: check::free_variables(&args, &body),
+                args,
+                body: Box::new(body),
+            });
+        // Arguments of formatting macros can be named, as in `x = 1`, but `x == 1` is a positional argument
+        let macro_arg = text::ident()
+            .padded()
+            .then_ignore(just('=').then(just('=').not().rewind()))
+            .or_not()
+            .then(expr.clone());
+        let r#macro = name
+            .then_ignore(just('!').padded())
             .then(
-                expr.clone()
+                macro_arg
                     .separated_by(just(','))
-                    // Allow trailing commas to appear in arg lists
                     .allow_trailing()
                     .delimited_by(just('('), just(')')),
             )
-            .map(|(f, args)| Expr::Call(f, args));
+            // Errors are emitted instead of failing, as `name!` can't be anything else and the error would be lost
+            .validate(|(name, args), span, emit| {
+                format_macro(name, args, span).unwrap_or_else(|err| {
+                    emit(err);
+                    Expr::Literal(Literal::Null)
+                })
+            });
         let array_index = expr
             .clone()
             .padded()
             .separated_by(just(','))
             .delimited_by(just('['), just(']'))
             .map(Expr::Array);
+        // A name followed by `::` is a path, left for [number_constant_parser()] to report,
+        // and a name followed by `!` that isn't `!=` is a macro
+        let var = name
+            .then(
+                just("::")
+                    .ignored()
+                    .or(just('!').padded().then(just('=').not()).ignored().rewind())
+                    .or_not(),
+            )
+            .try_map(|(name, path), span: Span| match path {
+                Some(_) => Err(Simple::custom(
+                    span,
+                    "paths are only allowed for numeric constants",
+                )),
+                None => Ok(name),
+            })
+            .map(Expr::Var);
 
         let atom = expr
             .clone()
             .delimited_by(just('('), just(')'))
-            .or(string)
-            .or(boolean)
-            .or(number)
-            .or(call)
-            .or(array_index)
-            .or(identifier
-                .then(expr.delimited_by(just('['), just(']')).or_not())
-                .map(|(name, index)| Expr::Var {
-                    name,
-                    index: Box::new(
-                        // index.unwrap_or(Expr::Literal(Literal::Num(Number::UInteger(0)))),
-                        index.unwrap_or(Expr::Literal(Literal::Num(Number::Integer(0)))),
-                    ),
-                }));
+            // Parenthesized expressions span their parentheses too
+            .map_with_span(|expr: Spanned<Expr>, span| Spanned::new(expr.node, span))
+            .or(string
+                .or(char_parser())
+                .or(boolean)
+                .or(number)
+                .or(number_constant_parser())
+                .or(r#loop.map(|s| Expr::Loop(Box::new(s))))
+                .or(closure)
+                .or(r#macro)
+                .or(array_index)
+                .or(var)
+                .map_with_span(Spanned::new))
+            .padded();
+
+        // Indexing and calls bind tighter than unary operators, so `-arr[0]` is `-(arr[0])` and `-x.len()` is `-(x.len())`
+        let args = expr
+            .clone()
+            .separated_by(just(','))
+            // Allow trailing commas to appear in arg lists
+            .allow_trailing()
+            .delimited_by(just('('), just(')'));
+        let postfix = atom
+            .then(
+                expr.clone()
+                    .delimited_by(just('['), just(']'))
+                    .map(Postfix::Index)
+                    .or(args.clone().map(Postfix::Call))
+                    .or(just('.')
+                        .ignore_then(name.map_with_span(Spanned::new).padded())
+                        .then(args)
+                        .map(|(method, args)| Postfix::Method(method, args)))
+                    .map_with_span(|postfix, span: Span| (postfix, span))
+                    .padded()
+                    .repeated(),
+            )
+            .foldl(|lhs, (postfix, postfix_span)| {
+                let span = lhs.span.start..postfix_span.end;
+                let expr = match postfix {
+                    Postfix::Index(index) => Expr::Index(Box::new(lhs), Box::new(index)),
+                    Postfix::Call(args) => Expr::Call(Box::new(lhs), args),
+                    Postfix::Method(method, args) => Expr::MethodCall {
+                        receiver: Box::new(lhs),
+                        method,
+                        args,
+                    },
+                };
+                Spanned::new(expr, span)
+            });
 
         let op = |c| just(c).padded();
 
-        let unary = op("-")
+        // `&` and `|` are not the start of `&&` and `||`
+        let bit_op = |c| just(c).then_ignore(just(c).not().rewind()).padded();
+
+        // A `-` before a number literal is part of it, unless the literal is indexed, called or has a method called on it,
+        // as those bind tighter. A `.` followed by another is the start of a range, as in `-1..1`
+        let negative_number = negative_number_parser()
+            .then_ignore(
+                text::whitespace()
+                    .ignore_then(
+                        one_of("[(")
+                            .ignored()
+                            .or(just('.').then(just('.').not()).ignored()),
+                    )
+                    .not()
+                    .ignored()
+                    .or(end())
+                    .rewind(),
+            )
+            .map_with_span(Spanned::new)
+            .padded();
+
+        let unary = negative_number
+            .clone()
+            .not()
+            .rewind()
+            .ignore_then(just('-'))
+            .to(Expr::Neg as UnaryOp)
+            .or(just('!').to(Expr::Not as UnaryOp))
+            .map_with_span(|op, span: Span| (op, span))
+            .padded()
             .repeated()
-            .then(atom)
-            .foldr(|_op, rhs| Expr::Neg(Box::new(rhs)));
+            .then(negative_number.or(postfix))
+            .foldr(|(op, op_span), rhs| {
+                let span = op_span.start..rhs.span.end;
+                Spanned::new(op(Box::new(rhs)), span)
+            });
 
-        let product = unary
+        // `as` binds tighter than binary operators, but looser than unary ones, so `-1 as f64` is `(-1) as f64`
+        let cast = unary
+            .then(
+                text::keyword("as")
+                    .padded()
+                    .ignore_then(number_type_parser())
+                    .map_with_span(|to, span: Span| (to, span))
+                    .repeated(),
+            )
+            .foldl(|lhs, (to, to_span)| {
+                let span = lhs.span.start..to_span.end;
+                Spanned::new(Expr::Cast(Box::new(lhs), to), span)
+            })
+            // Every precedence level holds two copies of the one below it, so its type doubles in size with each
+            // level. Boxing every few levels keeps it small enough for the compiler
+            .boxed();
+
+        // Not in Rust, which uses `pow()`. Binds tighter than the other binary operators, and right-associative,
+        // so `2 ** 3 ** 2` is `2 ** (3 ** 2)`. Unary operators bind tighter still: `-2 ** 2` is `(-2) ** 2`
+        let power = cast
+            .clone()
+            .then(op("**").ignore_then(cast).repeated())
+            .map(|(lhs, rest)| {
+                std::iter::once(lhs)
+                    .chain(rest)
+                    .rev()
+                    .reduce(|rhs, lhs| binary_op(lhs, (Expr::Pow as BinaryOp, rhs)))
+                    // There is always at least `lhs`
+                    .unwrap()
+            });
+
+        let product = power
             .clone()
             .then(
                 op("*")
-                    .to(Expr::Mul as fn(_, _) -> _)
-                    .or(op("/").to(Expr::Div as fn(_, _) -> _))
-                    .then(unary)
+                    .to(Expr::Mul as BinaryOp)
+                    .or(op("/").to(Expr::Div as BinaryOp))
+                    .or(op("%").to(Expr::Rem as BinaryOp))
+                    .then(power)
                     .repeated(),
             )
-            .foldl(|lhs, (op, rhs)| op(Box::new(lhs), Box::new(rhs)));
+            .foldl(binary_op);
 
         let sum = product
             .clone()
             .then(
                 op("+")
-                    .to(Expr::Add as fn(_, _) -> _)
-                    .or(op("-").to(Expr::Sub as fn(_, _) -> _))
+                    .to(Expr::Add as BinaryOp)
+                    .or(op("-").to(Expr::Sub as BinaryOp))
                     .then(product)
                     .repeated(),
             )
-            .foldl(|lhs, (op, rhs)| op(Box::new(lhs), Box::new(rhs)));
+            .foldl(binary_op)
+            .boxed();
 
-        let comparation = sum
+        let shift = sum
             .clone()
             .then(
-                op("==")
-                    .to(Expr::Eq as fn(_, _) -> _)
-                    .or(op("<").to(Expr::Lt as fn(_, _) -> _))
-                    .or(op(">").to(Expr::Gt as fn(_, _) -> _))
+                op("<<")
+                    .to(Expr::Shl as BinaryOp)
+                    .or(op(">>").to(Expr::Shr as BinaryOp))
                     .then(sum)
                     .repeated(),
             )
-            .foldl(|lhs, (op, rhs)| op(Box::new(lhs), Box::new(rhs)));
+            .foldl(binary_op);
+
+        let bit_and = shift
+            .clone()
+            .then(
+                bit_op("&")
+                    .to(Expr::BitAnd as BinaryOp)
+                    .then(shift)
+                    .repeated(),
+            )
+            .foldl(binary_op);
+
+        let bit_xor = bit_and
+            .clone()
+            .then(
+                op("^")
+                    .to(Expr::BitXor as BinaryOp)
+                    .then(bit_and)
+                    .repeated(),
+            )
+            .foldl(binary_op);
 
-        let bool_algebra = comparation
+        let bit_or = bit_xor
+            .clone()
+            .then(
+                bit_op("|")
+                    .to(Expr::BitOr as BinaryOp)
+                    .then(bit_xor)
+                    .repeated(),
+            )
+            .foldl(binary_op)
+            .boxed();
+
+        let comparation = bit_or
+            .clone()
+            .then(
+                op("==")
+                    .to(Expr::Eq as BinaryOp)
+                    .or(op("!=").to(Expr::Ne as BinaryOp))
+                    // Two-character operators go first, so `<=` isn't taken as `<` followed by `=`
+                    .or(op("<=").to(Expr::Le as BinaryOp))
+                    .or(op(">=").to(Expr::Ge as BinaryOp))
+                    .or(op("<").to(Expr::Lt as BinaryOp))
+                    .or(op(">").to(Expr::Gt as BinaryOp))
+                    .then(bit_or)
+                    .repeated(),
+            )
+            .foldl(binary_op);
+
+        // `&&` binds tighter than `||`, so `a || b && c` is `a || (b && c)`
+        let bool_and = comparation
             .clone()
             .then(
                 op("&&")
-                    .to(Expr::And as fn(_, _) -> _)
-                    .or(op("||").to(Expr::Or as fn(_, _) -> _))
+                    .to(Expr::And as BinaryOp)
                     .then(comparation)
                     .repeated(),
             )
-            .foldl(|lhs, (op, rhs)| op(Box::new(lhs), Box::new(rhs)));
+            .foldl(binary_op)
+            .boxed();
 
-        bool_algebra.padded()
+        let bool_or = bool_and
+            .clone()
+            .then(op("||").to(Expr::Or as BinaryOp).then(bool_and).repeated())
+            .foldl(binary_op);
+
+        let range = bool_or
+            .clone()
+            .then(
+                op("..=")
+                    .to(Expr::RangeInclusive as BinaryOp)
+                    .or(op("..").to(Expr::Range as BinaryOp))
+                    .then(bool_or)
+                    .or_not(),
+            )
+            .map(|(lhs, rhs)| match rhs {
+                Some(rhs) => binary_op(lhs, rhs),
+                None => lhs,
+            });
+
+        range.padded()
     })
 }
 
@@ -331,9 +1436,11 @@ pub fn expr_parser() -> impl Parser<char, Expr, Error = Simple<char>> + Clone {
 #[macro_export]
 macro_rules! parser {
     () => {
-        crate::parser::statement_block_item_loop_parser()
+        $crate::parser::statement_block_item_loop_parser()
             .2
-            .padded_by(crate::parser::comment_parser().padded().repeated())
+            .map_with_span($crate::parser::ast::Spanned::new)
+            .padded()
+            .padded_by($crate::parser::comment_parser().padded().repeated())
             .repeated()
             .padded()
     };
@@ -0,0 +1,130 @@
+//! Rustc-like reports for [Error]s, showing the source that caused them
+//!
+//! Built with [Ariadne](https://github.com/zesterer/ariadne), from the same authors as Chumsky
+
+use std::io::{self, Write};
+
+use ariadne::{Color, Config, Label, Report, ReportKind, Source};
+
+use crate::error::{token, Error, Span};
+
+/// Message for the label underlining the source of `err`
+fn label(err: &Error) -> String {
+    match err {
+        Error::Parse {
+            expected, found, ..
+        } => match expected.as_slice() {
+            [expected] => format!("expected {}", token(expected)),
+            _ => format!("unexpected {}", token(found)),
+        },
+        Error::InvalidSyntax { message, .. } => message.clone(),
+        Error::NoMain { .. } => "no `main` in here".to_string(),
+        Error::UndefinedVariable { .. } | Error::UndefinedFunction { .. } => {
+            "not found in this scope".to_string()
+        }
+        Error::AssignToImmutable { .. } => "cannot assign twice to immutable variable".to_string(),
+        Error::AssignToCaptured { .. } => {
+            "cannot assign to a variable captured by a closure".to_string()
+        }
+        Error::UndefinedMethod { type_name, .. } => {
+            format!("method not found in `{}`", type_name)
+        }
+        Error::ArityMismatch {
+            expected, found, ..
+        } => format!(
+            "expected {} argument{}, found {}",
+            expected,
+            if *expected == 1 { "" } else { "s" },
+            found
+        ),
+        Error::TypeMismatch {
+            expected, found, ..
+        } => format!("expected {}, found `{}`", expected, found),
+        Error::IndexOutOfBounds { index, .. } => format!("index {} is out of bounds", index),
+        Error::SliceOutOfBounds { start, end, .. } => {
+            format!("range {}..{} is out of bounds", start, end)
+        }
+        Error::NegativeIndex { .. } => "index is negative".to_string(),
+        Error::UnparsableValue { .. } => "parsed here".to_string(),
+        Error::CountTooLarge { limit, .. } => format!("must be at most {}", limit),
+        Error::DivisionByZero { .. } => "divisor evaluates to zero".to_string(),
+        Error::BreakOutsideLoop { .. } => "cannot `break` outside of a loop".to_string(),
+        Error::ContinueOutsideLoop { .. } => "cannot `continue` outside of a loop".to_string(),
+        Error::UndeclaredLabel { label, .. } => format!("undeclared label `'{}`", label),
+        Error::BreakWithValue { .. } => "can only break with a value inside `loop`".to_string(),
+        Error::Overflow { operation, .. } => format!("attempt to {} with overflow", operation),
+        Error::OperandOutOfRange { operand, .. } => format!("{} must be a `u32`", operand),
+        Error::RecursionLimit { .. } => "call nested too deeply".to_string(),
+        Error::NestingLimit { .. } => "nested too deeply".to_string(),
+    }
+}
+
+/// Extra information shown below the source, if there's anything useful to add
+fn note(err: &Error) -> Option<String> {
+    match err {
+        Error::NoMain { .. } => Some("evaluation starts from `fn main() { ... }`".to_string()),
+        Error::AssignToImmutable { name, .. } => Some(format!(
+            "only variables declared with `let mut` can be assigned to, as in `let mut {} = ...;`",
+            name
+        )),
+        Error::AssignToCaptured { name, .. } => Some(format!(
+            "the closure has a copy of `{}`, and changing it would leave the original as it was",
+            name
+        )),
+        Error::IndexOutOfBounds { len, .. } => Some(match len {
+            0 => "the indexed value is empty".to_string(),
+            len => format!("valid indices for this value are 0 to {}", len - 1),
+        }),
+        Error::SliceOutOfBounds { len, .. } => Some(format!(
+            "ranges of this value can go from 0 to {}, with the start no greater than the end",
+            len
+        )),
+        Error::NegativeIndex { .. } => Some("indices start at 0".to_string()),
+        Error::UnparsableValue { .. } => Some(
+            "`parse()` takes number literals, as in `42`, `-1.5` or `0xffu8`, and `true` or `false`"
+                .to_string(),
+        ),
+        Error::BreakWithValue { .. } => {
+            Some("`while` and `for` loops always evaluate to `()`".to_string())
+        }
+        Error::RecursionLimit { .. } => {
+            Some("a recursive function may be missing its base case".to_string())
+        }
+        Error::NestingLimit { limit, .. } if *limit == crate::NESTING_LIMIT => Some(
+            "the blocks and expressions of every call in a recursion add up".to_string(),
+        ),
+        _ => None,
+    }
+}
+
+/// Builds the report for `err`, coming from the file called `filename`
+pub fn report<'a>(err: &Error, filename: &'a str, color: bool) -> Report<'a, (&'a str, Span)> {
+    let span = err.span();
+    let mut builder = Report::build(ReportKind::Error, filename, span.start)
+        .with_config(Config::default().with_color(color))
+        .with_message(err)
+        .with_label(
+            Label::new((filename, span))
+                .with_message(label(err))
+                .with_color(Color::Red),
+        );
+    if let Some(note) = note(err) {
+        builder.set_note(note);
+    }
+    builder.finish()
+}
+
+/// Writes reports for every error in `errs` to `w`. `src` is the content of the file called `filename`
+pub fn write_reports<W: Write>(
+    errs: &[Error],
+    filename: &str,
+    src: &str,
+    color: bool,
+    mut w: W,
+) -> io::Result<()> {
+    let mut cache = (filename, Source::from(src));
+    for err in errs {
+        report(err, filename, color).write(&mut cache, &mut w)?;
+    }
+    Ok(())
+}
@@ -96,17 +96,26 @@ pub fn int() {
         vec![
             "0",
             "123132123",
-            // "123i32", "123usize", "123u32", "0usize", // "-1", // ?
-            // "1isize", "2usize",
-            // "123_u32",
-            // "0xff",
-            // "0xff_u8",
-            // "0o70",
-            // "0o70_i16",
-            // "0b1111_1111_1001_0000",
-            // "0b1111_1111_1001_0000i64",
-            // "0b________1",
-            // "0usize",
+            "123i32",
+            "123usize",
+            "123u32",
+            "0usize", // "-1", // ?
+            "1isize",
+            "2usize",
+            "255u8",
+            "18446744073709551615u64",
+            "123_u32",
+            "1_000_000",
+            "0xff",
+            "0xFF",
+            "0xff_u8",
+            "0o70",
+            "0o70_i16",
+            "0b1111_1111_1001_0000",
+            "0b1111_1111_1001_0000i64",
+            "0b________1",
+            "0usize",
+            "0xffff_ffff_ffff_ffff_u64",
         ],
         vec![
             // "0.1", // Should be a bad integer as soon as the parsers are separated
@@ -114,6 +123,15 @@ pub fn int() {
             "0,1",
             "0invalidSuffix",
             "123AFB43",
+            "256u8",
+            "128i8",
+            "0x100u8",
+            "0xffff_ffff_ffff_ffff",
+            "99999999999999999999999",
+            "0b12",
+            "0o8",
+            "0x",
+            "_1",
             "0b_",
             "0b____",
         ],
@@ -132,14 +150,21 @@ pub fn float() {
             "2.0",
             "2.",
             // ANCHOR[id=scientific-notation-test]
-            // "12E+99",
-            // "12.01E+99",
-            // "123.0f64", "0.1f64", "0.1f32", "5f32",
-            // "12E+99_f64",
-            // "12.01E+99_f64",
+            "12E+99",
+            "12.01E+99",
+            "1e10",
+            "2.5E-3",
+            "1_000.000_1",
+            "123.0f64",
+            "0.1f64",
+            "0.1f32",
+            "5f32",
+            "12E+99_f64",
+            "12.01E+99_f64",
         ],
         vec![
-            "2", "2f16", "2f8", "2.f64", "2,6", // "2.E+99"
+            "2", "2f16", "2f8", "2.f64", "2,6", "2.E+99", "1e", "1e+", "1e999", "1e39f32", "2.5u8",
+            ".5",
         ],
     );
 }
@@ -204,17 +229,90 @@ pub fn string() {
                 .then_ignore(end())
                 .parse_recovery_verbose(s)
         },
-        vec![r#""""#, r#""\""#, r#""uma string feliz :)""#],
+        vec![
+            r#""""#,
+            r#""uma string feliz :)""#,
+            r#""\"""#,
+            r#""a\"b""#,
+            r#""\n\r\t\0\\\'""#,
+            r#""\x41\x7f""#,
+            r#""\u{1F600}\u{0}\u{10_FFFF}""#,
+            "\"line\nbreak\"",
+            "\"continued \\\n    line\"",
+            r#"r"raw \n""#,
+            r#"r"""#,
+            r##"r#"say "hi""#"##,
+            r###"r##"a "# b"##"###,
+            r#"b"bytes""#,
+            r#"b"\xFF\x00\n""#,
+            r#"br"raw\bytes""#,
+            r##"br#"a"b"#"##,
+        ],
         vec![
             r#"string triste :("#,
             r#"""#,
             r#""string sem fechar"#,
+            r#""\""#,
+            r#""\q""#,
+            r#""\x80""#,
+            r#""\x4""#,
+            r#""\u{}""#,
+            r#""\u{1234567}""#,
+            r#""\u{D800}""#,
+            r#""\u{110000}""#,
+            r#""\u1234""#,
+            r#"r"a"""#,
+            r##"r#"a""##,
+            r###"r#"a"##"###,
+            r#"b"ação""#,
+            r#"b"\u{41}""#,
+            r#"br"ação""#,
             r#"''"#,
             r#"'test'"#,
         ],
     );
 }
 
+#[test]
+pub fn char() {
+    test_util::tests(
+        |s| {
+            crate::parser::char_parser()
+                .then_ignore(end())
+                .parse_recovery_verbose(s)
+        },
+        vec![
+            "'a'",
+            "'ç'",
+            "'😀'",
+            "'\"'",
+            r#"'\''"#,
+            r#"'\n'"#,
+            r#"'\\'"#,
+            r#"'\x7F'"#,
+            r#"'\u{e7}'"#,
+            "b'a'",
+            r#"b'\xFF'"#,
+            r#"b'\''"#,
+        ],
+        vec![
+            "''",
+            "'ab'",
+            "'''",
+            "'a",
+            "'a: loop {}",
+            r#"'\'"#,
+            "'\n'",
+            "'\t'",
+            r#"'\x80'"#,
+            r#""a""#,
+            "b'ç'",
+            r#"b'\u{41}'"#,
+            "b'ab'",
+        ],
+    );
+}
+
 #[test]
 pub fn assign() {
     test_util::tests(
@@ -233,11 +331,41 @@ pub fn assign() {
             r#"a = [1];"#,
             r#"a = [1, 2, 3];"#,
             r#"a = another_variable;"#,
+            // Compound assignments
+            r#"x += 1;"#,
+            r#"x -= y * 2;"#,
+            r#"x *= 2;"#,
+            r#"x /= 2;"#,
+            r#"x %= 2;"#,
+            r#"x **= 2;"#,
+            r#"x &= 0xf;"#,
+            r#"x |= 1;"#,
+            r#"x ^= 1;"#,
+            r#"x <<= 1;"#,
+            r#"x >>= 1;"#,
+            r#"x+=1;"#,
+            // Array elements
+            r#"arr[0] = 1;"#,
+            r#"arr[i + 1] = x;"#,
+            r#"m[i][j] = 0;"#,
+            r#"m [i] [j] = 0;"#,
+            r#"arr[0] += 1;"#,
+            r#"arr[arr[0]] = 1;"#,
         ],
         vec![
             r#"let a = "#,
             r#"name with space = "#,
-            r#"array[index] = "#, // TODO not yet implemented
+            r#"x + = 1;"#,
+            r#"x =+ 1;"#,
+            r#"x &&= true;"#,
+            r#"x <<<= 1;"#,
+            r#"x == 1;"#,
+            r#"x += ;"#,
+            r#"array[index] = "#,
+            r#"arr[] = 1;"#,
+            r#"arr[0 = 1;"#,
+            r#"[0] = 1;"#,
+            r#"f()[0] = 1;"#,
         ],
     );
 }
@@ -276,8 +404,60 @@ pub fn expr() {
             "1 - 1 == 1 - 1",
             "1 * 4 == 4 / 1",
             "- 1 *  -4 == 4 / 1",
+            "1 != 2",
+            "1 <= 2",
+            "1 >= 2",
+            "1 + 1 <= 2 * 1",
+            r#""a" != "b""#,
+            // Remainder, power and bitwise
+            "7 % 2",
+            "2 ** 3 ** 2",
+            "-2 ** 2",
+            "!true",
+            "!!x",
+            "1 & 2 | 3 ^ 4",
+            "1 << 2 >> 1",
+            "x & y == 0",
+            "a & b && c | d || e",
             "- - 1",
             "--1",
+            // Indexing
+            "arr[0]",
+            "arr[i + 1]",
+            "m[0][1]",
+            "m [0] [1]",
+            "[1, 2][0]",
+            "f(x)[0]",
+            "(a)[0]",
+            "-arr[0]",
+            "arr[arr[0]]",
+            r#""abc"[1]"#,
+            r#""abc"[0..2]"#,
+            "s[i..=j]",
+            // Formatting macros
+            r#"format!("{}", 1)"#,
+            r#"format!("{} {x} {0:>5.2?}", 1,)"#,
+            r#"format!("{a}", a = 1)"#,
+            r#"format!("{}", x == 1)"#,
+            r#"print!("{{}}")"#,
+            "println!()",
+            r#"eprintln! ("{:.*}", 2, x)"#,
+            r#"format!("a").len()"#,
+            "x != y",
+            "!x",
+            // Methods
+            "s.len()",
+            "s . len ()",
+            r#""a,b".split(',')"#,
+            r#"s.replace("a", "b",)"#,
+            "s.trim().len()",
+            "-s.len()",
+            "xs[0].len() + 1",
+            "0..s.len()",
+            "'a' < 'b'",
+            "'a' as u8",
+            r#"b"abc"[0]"#,
+            "'a: loop {break 'a 'a';}",
             // Bool algebra
             "true",
             "false",
@@ -292,6 +472,34 @@ pub fn expr() {
             r#"array[2 + 3]"#,
             r#"array[array_index]"#,
             r#"array[array_index + array_indexes[2]] + array[array_index + array_indexes[3]]"#,
+            // Loop
+            "loop {}",
+            "loop {break 5;} + 1",
+            "'a: loop {break 'a 5;}",
+            // Range
+            "0..10",
+            "0 .. 10",
+            "0..=10",
+            "x..y + 1",
+            "0.5..1",
+            // Cast
+            "1 as f64",
+            "1.5 as isize",
+            "-x as f64",
+            "1 + 2 as f64 * 3.0",
+            "x as isize as f64",
+            // Constants
+            "f64::NAN",
+            "f32::INFINITY + u8::MAX as f32",
+            // Closures and calls
+            "|x| x + 1",
+            "|| 1",
+            "|a, b| { a + b; }",
+            "|x| |y| x + y",
+            "f(x)(y)",
+            "fs[0](1)",
+            "(|x| x)(1)",
+            "apply(|x| x * 2, 3)",
         ],
         vec![
             "1+",
@@ -313,6 +521,49 @@ pub fn expr() {
             "/",
             "*",
             "1 === 1",
+            "0..",
+            "..10",
+            "0...10",
+            "1 as",
+            "as f64",
+            "1 as 2",
+            "f64::PI",
+            "f64::",
+            "bool::MAX",
+            "1 =! 2",
+            "1 < = 2",
+            "1 =< 2",
+            "1 !== 2",
+            "1 %",
+            "2 **",
+            "2 *** 2",
+            "1 <<< 2",
+            "1 &",
+            "!",
+            "1 ! 2",
+            "arr[",
+            "arr[]",
+            "arr[0",
+            "arr]0[",
+            "s.len",
+            r#"format!("{}")"#,
+            r#"format!("}")"#,
+            r#"format!("{:q}", 1)"#,
+            r#"format!("{}", a = 1, 2)"#,
+            r#"format!("{a}", a = 1, a = 2)"#,
+            r#"format!(b"bytes")"#,
+            r#"print!()"#,
+            r#"todo!()"#,
+            r#"format!("{} {x:.}", 1)"#,
+            r#"format!("{:w}", 1)"#,
+            "s.()",
+            "s.len(",
+            "s.0()",
+            "|x x",
+            "|x|",
+            "|,| 1",
+            "f(",
+            "f(1,,2)",
         ],
     )
 }
@@ -384,8 +635,29 @@ pub fn statement() {
             "if add(x, y) - 5 / 3 {}",
             r#"if "string" {}"#, // This is sintatically valid but will occur in a runtime error
             r#"if "string" {} else {false;}"#, // This is sintatically valid but will occur in a runtime error
+            "return;",
+            "return 1 + 2;",
+            "return add(x, y);",
+            "return x",
+            "break;",
+            "break 'outer;",
+            "break 1 + 2;",
+            "break 'outer 1 + 2;",
+            "continue;",
+            "continue 'outer;",
+            "let x = loop {break 5;};",
+            "let mut x = 1;",
+            "let  mut  x = 1;",
+            "let mutable = 1;",
+        ],
+        vec![
+            "12",
+            r#""Termina sem ;""#,
+            "if 0121 {}",
+            "let mut = 1;",
+            "let mutx = ;",
+            "mut x = 1;",
         ],
-        vec!["12", r#""Termina sem ;""#, "if 0121 {}"],
     );
 }
 
@@ -412,6 +684,14 @@ pub fn block() {
             "{
                 add(x);
             }",
+            "{fn f() {} f();}",
+            "{
+                f();
+                fn f(x) {
+                    fn g() {}
+                    g();
+                }
+            }",
             "{
                 // comentão
                 add(x);
@@ -449,8 +729,84 @@ pub fn r#loop() {
                 body;
                 break;
             }",
+            "loop {
+                break 10;
+            }",
+            "'outer: loop {
+                loop {
+                    break 'outer;
+                }
+            }",
+            "'outer: loop {
+                loop {
+                    continue 'outer;
+                }
+            }",
+            "'outer : loop {break 'outer x + 1;}",
+        ],
+        vec![
+            "loop (cond) {{}}",
+            "loop",
+            "{}",
+            "outer: loop {}",
+            "'outer loop {}",
+            "' outer: loop {}",
+        ],
+    );
+}
+
+#[test]
+pub fn r#while() {
+    test_util::tests(
+        |s| {
+            crate::parser::statement_block_item_loop_parser()
+                .4
+                .then_ignore(end())
+                .parse_recovery_verbose(s)
+        },
+        vec![
+            "while true {}",
+            "while x < 10 {x = x + 1;}",
+            "while (x < 10) {statement();}",
+            "while x < 10 && y {
+                body;
+                break;
+            }",
+            "'outer: while true {continue 'outer;}",
+        ],
+        vec!["while {}", "while true", "while true ;", "while x < 10 {"],
+    );
+}
+
+#[test]
+pub fn r#for() {
+    test_util::tests(
+        |s| {
+            crate::parser::statement_block_item_loop_parser()
+                .5
+                .then_ignore(end())
+                .parse_recovery_verbose(s)
+        },
+        vec![
+            "for x in 0..10 {}",
+            "for x in 0..=10 {x;}",
+            "for i in 1 + 1..n * 2 {statement(i);}",
+            "for element in array {
+                if element == 0 {
+                    continue;
+                }
+                break;
+            }",
+            "for _ in [1, 2, 3] {}",
+            "'rows: for i in 0..3 {for j in 0..3 {continue 'rows;}}",
+        ],
+        vec![
+            "for x 0..10 {}",
+            "for in 0..10 {}",
+            "for x in {}",
+            "for x in 0..10",
+            "for 1 in 0..10 {}",
         ],
-        vec!["loop (cond) {{}}", "loop", "{}"],
     );
 }
 
@@ -1,3 +1,4 @@
+use zeca::error::Error;
 use zeca::parser::ast::{Literal, Number};
 
 fn parse_file<P>(path: P) -> Literal
@@ -11,6 +12,13 @@ where
     parsed.unwrap()
 }
 
+fn eval_err(src: &str) -> Vec<Error> {
+    let parsed = zeca::eval_source(src.to_string());
+    println!("Parse result: {:?}", parsed);
+    assert!(parsed.is_err());
+    parsed.unwrap_err()
+}
+
 #[test]
 fn simple() {
     let expected_value = 13.06;
@@ -34,6 +42,411 @@ fn array() {
     assert!(val == expected_value);
 }
 
+#[test]
+fn indexing() {
+    let val = parse_file("tests/examples/good/indexing.zeca");
+    assert!(
+        val == Literal::Array(vec![
+            Literal::Num(Number::Integer(10)),
+            Literal::Num(Number::Integer(30)),
+            Literal::Num(Number::Integer(6)),
+            Literal::Num(Number::Integer(-20)),
+            Literal::Num(Number::Integer(2)),
+            Literal::Num(Number::Integer(20)),
+            Literal::Char('é'),
+            Literal::Num(Number::Integer(60)),
+        ])
+    );
+    let errs = eval_err("fn main() { let arr = [1, 2, 3]; arr[3]; }");
+    assert!(matches!(
+        &errs[..],
+        [Error::IndexOutOfBounds { index: 3, len: 3, span }] if *span == (33..39)
+    ));
+    assert!(errs[0].to_string().contains("the len is 3"));
+    let errs = eval_err(r#"fn main() { "héllo"[5]; }"#);
+    assert!(matches!(
+        &errs[..],
+        [Error::IndexOutOfBounds {
+            index: 5,
+            len: 5,
+            ..
+        }]
+    ));
+    let errs = eval_err("fn main() { let arr = [1]; let i = -1; arr[i]; }");
+    assert!(matches!(&errs[..], [Error::NegativeIndex { span }] if *span == (43..44)));
+    let errs = eval_err("fn main() { let arr = [1]; arr[0.5]; }");
+    assert!(
+        matches!(&errs[..], [Error::TypeMismatch { expected, found, .. }] if expected == "an integer" && found == "f64")
+    );
+    let errs = eval_err("fn main() { true[0]; }");
+    assert!(
+        matches!(&errs[..], [Error::TypeMismatch { found, span, .. }] if found == "bool" && *span == (12..16))
+    );
+}
+
+#[test]
+fn scoping() {
+    let val = parse_file("tests/examples/good/scoping.zeca");
+    let expected = [22, 2, 0, 3];
+    assert!(
+        val == Literal::Array(
+            expected
+                .into_iter()
+                .map(|x| Literal::Num(Number::Integer(x)))
+                .collect()
+        )
+    );
+    let errs = eval_err("fn main() { { let inner = 1; } inner; }");
+    assert!(
+        matches!(&errs[..], [Error::UndefinedVariable { name, span }] if name == "inner" && *span == (31..36))
+    );
+    let errs = eval_err("fn main() { x = 1; }");
+    assert!(
+        matches!(&errs[..], [Error::UndefinedVariable { name, span }] if name == "x" && *span == (12..13))
+    );
+    // Functions only see their arguments, not the variables of their callers
+    let errs = eval_err("fn f() { x; } fn main() { let x = 1; f(); }");
+    assert!(matches!(&errs[..], [Error::UndefinedVariable { name, .. }] if name == "x"));
+    let errs = eval_err("fn f() { x = 2; } fn main() { let x = 1; f(); }");
+    assert!(matches!(&errs[..], [Error::UndefinedVariable { name, .. }] if name == "x"));
+}
+
+#[test]
+fn immutability() {
+    let errs = eval_err("fn main() { let x = 1; x = 2; }");
+    assert!(
+        matches!(&errs[..], [Error::AssignToImmutable { name, span }] if name == "x" && *span == (23..24))
+    );
+    // Every offending assignment is reported, and nothing is evaluated
+    let errs = eval_err("fn main() { let arr = [1]; arr[0] += 1; let y = 1 / 0; y = 2; }");
+    assert!(matches!(
+        &errs[..],
+        [Error::AssignToImmutable { span: first, .. }, Error::AssignToImmutable { span: second, .. }]
+            if *first == (27..33) && *second == (55..56)
+    ));
+    // Function arguments and loop variables can't be assigned to either
+    let errs = eval_err("fn f(x) { x = 1; } fn main() { for i in 0..3 { i = 1; } }");
+    assert!(matches!(
+        &errs[..],
+        [Error::AssignToImmutable { name: x, .. }, Error::AssignToImmutable { name: i, .. }]
+            if x == "x" && i == "i"
+    ));
+    // Shadowing decides which binding is assigned to
+    let errs = eval_err("fn main() { let mut x = 1; { let x = 2; x = 3; } }");
+    assert!(matches!(&errs[..], [Error::AssignToImmutable { .. }]));
+    let val =
+        zeca::eval_source("fn main() { let x = 1; { let mut x = 2; x = 3; } x; }".to_string());
+    assert!(val == Ok(Literal::Num(Number::Integer(1))));
+    let val = zeca::eval_source("fn main() { let x = 1; let mut x = x; x += 1; x; }".to_string());
+    assert!(val == Ok(Literal::Num(Number::Integer(2))));
+
+    let src = "fn main() {\n    let x = 1;\n    x = 2;\n}";
+    let mut output = Vec::new();
+    zeca::report::write_reports(&eval_err(src), "test.zeca", src, false, &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("cannot assign twice to immutable variable `x`"));
+    assert!(output.contains("test.zeca:3:5"));
+    assert!(output.contains("let mut x"));
+}
+
+#[test]
+fn nested_functions() {
+    let val = parse_file("tests/examples/good/nested_fn.zeca");
+    assert!(
+        val == Literal::Array(vec![
+            Literal::Num(Number::Integer(16)),
+            Literal::Num(Number::Integer(120)),
+            Literal::Bool(true),
+            Literal::Bool(true),
+            Literal::Num(Number::Integer(1)),
+            Literal::Num(Number::Integer(100)),
+            Literal::Num(Number::Integer(9)),
+        ])
+    );
+    // Functions see the items where they are declared, not where they are called
+    let val = zeca::eval_source(
+        "fn main() { fn value() { 1; } fn get() { value(); } { fn value() { 2; } get(); } }"
+            .to_string(),
+    );
+    assert!(val == Ok(Literal::Num(Number::Integer(1))));
+    let errs = eval_err("fn main() { { fn f() {} } f(); }");
+    assert!(matches!(&errs[..], [Error::UndefinedFunction { name, .. }] if name == "f"));
+    let errs = eval_err("fn g() { f(); } fn main() { fn f() {} g(); }");
+    assert!(matches!(&errs[..], [Error::UndefinedFunction { name, .. }] if name == "f"));
+    let errs = eval_err("fn main() { let x = 1; fn f() { x; } f(); }");
+    assert!(matches!(&errs[..], [Error::UndefinedVariable { name, .. }] if name == "x"));
+    let errs = eval_err("fn main() { fn f(x) { x = 1; } }");
+    assert!(matches!(&errs[..], [Error::AssignToImmutable { name, .. }] if name == "x"));
+    let errs = eval_err("fn main() { fn f(x) { x; } f(); }");
+    assert!(matches!(
+        &errs[..],
+        [Error::ArityMismatch { name, expected: 1, found: 0, .. }] if name == "f"
+    ));
+}
+
+#[test]
+fn string_literals() {
+    let val = parse_file("tests/examples/good/string_literals.zeca");
+    assert!(
+        val == Literal::Array(vec![
+            Literal::Str("say \"hi\"\tand\\leave\n".to_string()),
+            Literal::Str(r#"say "hi""#.to_string()),
+            Literal::Str("éA".to_string()),
+            Literal::Str("one line".to_string()),
+            Literal::Array(vec![
+                Literal::Num(Number::U8(b'a')),
+                Literal::Num(Number::U8(0xFF)),
+            ]),
+            Literal::Num(Number::U8(b'\n')),
+            Literal::Char('ç'),
+            Literal::Bool(true),
+            Literal::Num(Number::U8(97)),
+            Literal::Bool(true),
+        ])
+    );
+    let errs = eval_err("fn main() { 'a' < \"a\"; }");
+    assert!(matches!(
+        &errs[..],
+        [Error::TypeMismatch { expected, found, .. }] if expected == "`char`" && found == "str"
+    ));
+    let errs = eval_err(r#"fn main() { "\x80"; }"#);
+    assert!(matches!(&errs[..], [Error::InvalidSyntax { span, .. }] if *span == (13..17)));
+}
+
+#[test]
+fn strings() {
+    let val = parse_file("tests/examples/good/strings.zeca");
+    let str = |s: &str| Literal::Str(s.to_string());
+    assert!(
+        val == Literal::Array(vec![
+            str("Olá, mundo!"),
+            Literal::Num(Number::UInteger(11)),
+            str("Olá"),
+            str("mundo"),
+            Literal::Array(vec![str("a"), str("b"), str(""), str("c")]),
+            Literal::Num(Number::UInteger(4)),
+            str("1,2,3"),
+            str("SHOUT"),
+            str("zeca"),
+            Literal::Bool(true),
+            Literal::Bool(false),
+            Literal::Num(Number::Integer(25)),
+            Literal::Num(Number::Float(5.0)),
+            Literal::Bool(true),
+            Literal::Bool(true),
+            Literal::Array(vec![
+                Literal::Num(Number::Integer(2)),
+                Literal::Num(Number::Integer(3)),
+            ]),
+        ])
+    );
+    let errs = eval_err(r#"fn main() { "abc"[1..4]; }"#);
+    assert!(matches!(
+        &errs[..],
+        [Error::SliceOutOfBounds { start: 1, end: 4, len: 3, span }] if *span == (12..23)
+    ));
+    let errs = eval_err(r#"fn main() { "abc"[2..1]; }"#);
+    assert!(matches!(
+        &errs[..],
+        [Error::SliceOutOfBounds {
+            start: 2,
+            end: 1,
+            ..
+        }]
+    ));
+    assert!(errs[0].to_string().contains("starts at 2 but ends at 1"));
+    let errs = eval_err(r#"fn main() { "abc"[-1..1]; }"#);
+    assert!(matches!(&errs[..], [Error::NegativeIndex { span }] if *span == (18..23)));
+    let errs = eval_err(r#"fn main() { "abc".size(); }"#);
+    assert!(matches!(
+        &errs[..],
+        [Error::UndefinedMethod { name, type_name, span }] if name == "size" && type_name == "str" && *span == (18..22)
+    ));
+    let errs = eval_err("fn main() { true.len(); }");
+    assert!(matches!(
+        &errs[..],
+        [Error::UndefinedMethod { type_name, .. }] if type_name == "bool"
+    ));
+    let errs = eval_err(r#"fn main() { "abc".contains(); }"#);
+    assert!(matches!(
+        &errs[..],
+        [Error::ArityMismatch {
+            expected: 1,
+            found: 0,
+            ..
+        }]
+    ));
+    let errs = eval_err(r#"fn main() { "abc".contains(1); }"#);
+    assert!(matches!(
+        &errs[..],
+        [Error::TypeMismatch { expected, span, .. }] if expected == "a `str` or `char`" && *span == (27..28)
+    ));
+    let errs = eval_err(r#"fn main() { "12a".parse(); }"#);
+    assert!(matches!(&errs[..], [Error::UnparsableValue { text, .. }] if text == "12a"));
+    let errs = eval_err(r#"fn main() { "a" + 1; }"#);
+    assert!(matches!(
+        &errs[..],
+        [Error::TypeMismatch { expected, found, .. }] if expected == "`str`" && found == "isize"
+    ));
+}
+
+#[test]
+fn formatting() {
+    let val = parse_file("tests/examples/good/formatting.zeca");
+    let expected = [
+        "Hello, ZECA!",
+        "ZECA has 4 letters",
+        "a b a",
+        "Oi, ZECA",
+        r#""quote\"d\n" 'c' 1.0 [1, 2]"#,
+        "1 c true [1, 2]",
+        "3.14 3 3.1",
+        "[    1] [2    ] [  3  ] [    4] [x    ]",
+        "[**mid**] [1---]",
+        "+1 -1 -0042 +003.1",
+        "[     7] [  3] [    ZE]",
+        "{} {1}",
+        "tru",
+    ];
+    assert!(
+        val == Literal::Array(
+            expected
+                .iter()
+                .map(|s| Literal::Str(s.to_string()))
+                .collect()
+        )
+    );
+
+    // Format strings are checked before running, and errors point at them
+    let errs = eval_err(r#"fn main() { format!("{} {}", 1); }"#);
+    assert!(matches!(
+        &errs[..],
+        [Error::InvalidSyntax { message, span }]
+            if message == "invalid reference to positional argument 1 (there is 1 argument)" && *span == (20..27)
+    ));
+    let errs = eval_err(r#"fn main() { println!("{}", 1, 2); }"#);
+    assert!(matches!(
+        &errs[..],
+        [Error::InvalidSyntax { message, .. }] if message == "argument 1 is never used"
+    ));
+    let errs = eval_err(r#"fn main() { println!("{", 1); }"#);
+    assert!(matches!(
+        &errs[..],
+        [Error::InvalidSyntax { message, .. }] if message.starts_with("unclosed `{`")
+    ));
+    let errs = eval_err(r#"fn main() { println!("{:x}", 1); }"#);
+    assert!(matches!(
+        &errs[..],
+        [Error::InvalidSyntax { message, .. }] if message == "unknown format trait `x`"
+    ));
+    let errs = eval_err(r#"fn main() { printline!("hi"); }"#);
+    assert!(matches!(
+        &errs[..],
+        [Error::InvalidSyntax { message, span }] if message == "cannot find macro `printline`" && *span == (12..28)
+    ));
+    let errs = eval_err("fn main() { let x = 1; println!(x); }");
+    assert!(matches!(
+        &errs[..],
+        [Error::InvalidSyntax { message, .. }] if message == "format argument must be a string literal"
+    ));
+    let errs = eval_err(r#"fn main() { println!("{y}"); }"#);
+    assert!(matches!(
+        &errs[..],
+        [Error::UndefinedVariable { name, span }] if name == "y" && *span == (21..26)
+    ));
+    let errs = eval_err(r#"fn main() { println!("{:1$}", 1, -2); }"#);
+    assert!(matches!(
+        &errs[..],
+        [Error::TypeMismatch { expected, span, .. }] if expected == "a `usize`" && *span == (33..35)
+    ));
+
+    // Values are displayed like Rust does
+    assert_eq!(Literal::Num(Number::Float(1.0)).to_string(), "1");
+    assert_eq!(Literal::Num(Number::Float(1.0)).debug().to_string(), "1.0");
+    assert_eq!(Literal::Str("a\"b".to_string()).to_string(), "a\"b");
+    assert_eq!(
+        Literal::Str("a\"b".to_string()).debug().to_string(),
+        r#""a\"b""#
+    );
+    assert_eq!(Literal::Null.to_string(), "()");
+    let range = |inclusive| Literal::Range {
+        start: 0,
+        end: 10,
+        inclusive,
+    };
+    assert_eq!(range(false).to_string(), "0..10");
+    assert_eq!(range(true).to_string(), "0..=10");
+    assert_eq!(
+        Literal::Array(vec![Literal::Char('a'), Literal::Bool(false)]).to_string(),
+        "['a', false]"
+    );
+}
+
+#[test]
+fn closures() {
+    let val = parse_file("tests/examples/good/closures.zeca");
+    assert!(
+        val == Literal::Array(vec![
+            Literal::Num(Number::Integer(11)),
+            Literal::Num(Number::Integer(9)),
+            Literal::Num(Number::Integer(15)),
+            Literal::Num(Number::Integer(20)),
+            Literal::Num(Number::Integer(7)),
+            Literal::Num(Number::Integer(3)),
+            Literal::Num(Number::Integer(1)),
+            Literal::Num(Number::Integer(6)),
+        ])
+    );
+    let errs = eval_err("fn main() { let x = 1; x(); }");
+    assert!(matches!(
+        &errs[..],
+        [Error::TypeMismatch { expected, span, .. }] if expected == "a function" && *span == (23..24)
+    ));
+    let errs = eval_err("fn main() { let f = |x| x; f(1, 2); }");
+    assert!(matches!(
+        &errs[..],
+        [Error::ArityMismatch {
+            expected: 1,
+            found: 2,
+            ..
+        }]
+    ));
+    // Closures get a copy of the variables around them, which they can't assign to
+    let src = "fn main() { let mut x = 1; let f = || { x = 2; }; }";
+    let errs = eval_err(src);
+    assert!(
+        matches!(&errs[..], [Error::AssignToCaptured { name, span }] if name == "x" && *span == (40..41))
+    );
+    let mut output = Vec::new();
+    zeca::report::write_reports(&errs, "test.zeca", src, false, &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("cannot assign to captured variable `x`; closures capture by value"));
+    assert!(output.contains("the closure has a copy of `x`"));
+    let errs = eval_err("fn main() { let mut x = 1; let f = || { let g = || { x += 1; }; }; }");
+    assert!(matches!(&errs[..], [Error::AssignToCaptured { name, .. }] if name == "x"));
+    let errs = eval_err("fn main() { let x = 1; let f = || { x = 2; }; }");
+    assert!(matches!(&errs[..], [Error::AssignToImmutable { name, .. }] if name == "x"));
+    let val = zeca::eval_source(
+        "fn main() { let mut x = 1; let f = || { let mut x = x; x += 1; x; }; f() + x; }"
+            .to_string(),
+    );
+    assert!(val == Ok(Literal::Num(Number::Integer(3))));
+    let errs = eval_err("fn main() { let f = |x| { x += 1; }; }");
+    assert!(matches!(&errs[..], [Error::AssignToImmutable { name, .. }] if name == "x"));
+    // Closures only capture what they use, so a large value around them isn't copied each time one is made or called
+    let val = zeca::eval_source(
+        "fn main() {
+            let mut big = [0];
+            for i in 0..16 { big = [big, big]; }
+            let mut sum = 0;
+            for i in 0..20000 { let add = |x| x + i; sum = add(sum); }
+            sum;
+        }"
+        .to_string(),
+    );
+    assert!(val == Ok(Literal::Num(Number::Integer(199990000))));
+}
+
 // FIXME block need to return eval of last element
 #[test]
 fn negation() {
@@ -84,6 +497,757 @@ fn loop_breaks() {
     assert!(val == expected_value);
 }
 
+#[test]
+fn while_loop() {
+    let expected_value = 16;
+    let val = parse_file("tests/examples/good/while.zeca");
+    let val = match val {
+        Literal::Num(Number::Integer(x)) => x,
+        _ => panic!("Expected value is not Integer type"),
+    };
+    assert!(val == expected_value);
+}
+
+#[test]
+fn while_non_boolean_condition() {
+    let errs = eval_err("fn main() { while 1 { break; } }");
+    assert!(matches!(&errs[..], [Error::TypeMismatch { found, .. }] if found == "isize"));
+}
+
+#[test]
+fn for_loop() {
+    let expected_value = 44;
+    let val = parse_file("tests/examples/good/for.zeca");
+    let val = match val {
+        Literal::Num(Number::Integer(x)) => x,
+        _ => panic!("Expected value is not Integer type"),
+    };
+    assert!(val == expected_value);
+}
+
+#[test]
+fn for_huge_ranges() {
+    // Ranges are not collected before iterating, so these stop as soon as they break
+    for (src, expected) in [
+        ("fn main() { let mut n = 0; for i in 0..9223372036854775807 { n = i; if i == 3 { break; } } n; }", 3),
+        ("fn main() { let mut n = 0; for i in 0..100000000000 { n = i; if i == 3 { break; } } n; }", 3),
+        ("fn main() { let mut n = 0; for i in 9223372036854775804..=9223372036854775807 { n += 1; } n; }", 4),
+    ] {
+        let val = zeca::eval_source(src.to_string());
+        assert!(val == Ok(Literal::Num(Number::Integer(expected))));
+    }
+    let val = zeca::eval_source(r#"fn main() { "abc"[0..=2]; }"#.to_string());
+    assert!(val == Ok(Literal::Str("abc".to_string())));
+    let val = zeca::eval_source("fn main() { [1, 2][0..=9223372036854775807]; }".to_string());
+    assert!(matches!(&val, Err(errs) if matches!(&errs[..], [Error::SliceOutOfBounds { .. }])));
+}
+
+#[test]
+fn for_non_iterable() {
+    let errs = eval_err("fn main() { for x in true {} }");
+    assert!(matches!(&errs[..], [Error::TypeMismatch { found, .. }] if found == "bool"));
+}
+
+#[test]
+fn early_return() {
+    let expected_value = 4;
+    let val = parse_file("tests/examples/good/return.zeca");
+    let val = match val {
+        Literal::Num(Number::Integer(x)) => x,
+        _ => panic!("Expected value is not Integer type"),
+    };
+    assert!(val == expected_value);
+}
+
+#[test]
+fn loop_labels() {
+    let expected_value = 140;
+    let val = parse_file("tests/examples/good/labels.zeca");
+    let val = match val {
+        Literal::Num(Number::Integer(x)) => x,
+        _ => panic!("Expected value is not Integer type"),
+    };
+    assert!(val == expected_value);
+}
+
+#[test]
+fn loop_control_errors() {
+    let errs = eval_err("fn main() { break; }");
+    assert!(matches!(&errs[..], [Error::BreakOutsideLoop { .. }]));
+    let errs = eval_err("fn main() { continue; }");
+    assert!(matches!(&errs[..], [Error::ContinueOutsideLoop { .. }]));
+    let errs = eval_err("fn main() { loop { break 'nowhere; } }");
+    assert!(matches!(&errs[..], [Error::UndeclaredLabel { label, .. }] if label == "nowhere"));
+    let errs = eval_err("fn main() { while true { break 1; } }");
+    assert!(matches!(&errs[..], [Error::BreakWithValue { .. }]));
+}
+
+#[test]
+fn evaluation_errors() {
+    let errs = eval_err("fn main() { x; }");
+    assert!(matches!(&errs[..], [Error::UndefinedVariable { name, .. }] if name == "x"));
+    let errs = eval_err("fn main() { foo(); }");
+    assert!(matches!(&errs[..], [Error::UndefinedFunction { name, .. }] if name == "foo"));
+    let errs = eval_err("fn add(x, y) { x + y; } fn main() { add(1); }");
+    assert!(matches!(
+        &errs[..],
+        [Error::ArityMismatch { name, expected: 2, found: 1, .. }] if name == "add"
+    ));
+    let errs = eval_err(r#"fn main() { -"string"; }"#);
+    assert!(matches!(&errs[..], [Error::TypeMismatch { found, .. }] if found == "str"));
+    let errs = eval_err("fn main() { let x = [1, 2]; x[5]; }");
+    assert!(matches!(
+        &errs[..],
+        [Error::IndexOutOfBounds {
+            index: 5,
+            len: 2,
+            ..
+        }]
+    ));
+    let errs = eval_err("fn main() { 1 / 0; }");
+    assert!(matches!(&errs[..], [Error::DivisionByZero { .. }]));
+    let errs = eval_err("fn not_main() {}");
+    assert!(matches!(&errs[..], [Error::NoMain { span }] if *span == (0..16)));
+}
+
+#[test]
+fn numbers() {
+    let expected_value = 14.25;
+    let delta = 1e-10;
+    let val = parse_file("tests/examples/good/numbers.zeca");
+    let val = match val {
+        Literal::Num(Number::F64(x)) => x,
+        _ => panic!("Returned value is not a F64 type"),
+    };
+    assert!(f64::abs(val - expected_value) < delta);
+}
+
+#[test]
+fn sized_integers() {
+    let expected_value = 4;
+    let val = parse_file("tests/examples/good/sized.zeca");
+    let val = match val {
+        Literal::Num(Number::U8(x)) => x,
+        _ => panic!("Returned value is not a U8 type"),
+    };
+    assert!(val == expected_value);
+    let errs = eval_err("fn main() { 200u8 + 56; }");
+    assert!(matches!(
+        &errs[..],
+        [Error::Overflow {
+            operation: "add",
+            ..
+        }]
+    ));
+    let errs = eval_err("fn main() { 1u8 + 1i8; }");
+    assert!(
+        matches!(&errs[..], [Error::TypeMismatch { expected, found, .. }] if expected == "`u8`" && found == "i8")
+    );
+    let errs = eval_err("fn main() { 1u8 + 300; }");
+    assert!(matches!(
+        &errs[..],
+        [Error::Overflow {
+            operation: "convert",
+            ..
+        }]
+    ));
+    let errs = eval_err("fn main() { -1u32; }");
+    assert!(matches!(&errs[..], [Error::TypeMismatch { found, .. }] if found == "u32"));
+    let errs = eval_err("fn main() { 256u8; }");
+    assert!(matches!(&errs[..], [Error::InvalidSyntax { .. }]));
+    // Only literals without a suffix adapt, not ones written as `isize`
+    for src in [
+        "fn main() { 5isize + 3u8; }",
+        "fn main() { 5i64 + 3u8; }",
+        "fn main() { (300 as isize) + 3u8; }",
+    ] {
+        let errs = eval_err(src);
+        assert!(
+            matches!(&errs[..], [Error::TypeMismatch { found, .. }] if found == "u8"),
+            "{}",
+            src
+        );
+    }
+    // A `-` is part of the literal it comes before
+    let val = zeca::eval_source("fn main() { [-128i8, -9223372036854775808, -1..1]; }".to_string());
+    assert!(matches!(
+        &val.as_ref().map(|val| val.to_string()).as_deref(),
+        Ok("[-128, -9223372036854775808, -1..1]")
+    ));
+    let errs = eval_err("fn main() { -129i8; }");
+    assert!(
+        matches!(&errs[..], [Error::InvalidSyntax { message, .. }] if message.contains("too large for `i8`"))
+    );
+    // Unless the literal has a method called on it, which binds tighter
+    let errs = eval_err("fn main() { -128i8.len(); }");
+    assert!(
+        matches!(&errs[..], [Error::InvalidSyntax { message, .. }] if message.contains("too large for `i8`"))
+    );
+}
+
+#[test]
+fn radix_literals() {
+    let val = zeca::eval_source(
+        "fn main() { 0xff + 0o17 + 0b1010 + 1_000 + 0x1_0u8 as isize; }".to_string(),
+    );
+    assert!(matches!(val, Ok(Literal::Num(Number::Isize(1296)))));
+    let errs = eval_err("fn main() { 0x1_0000_0000_0000_0000; }");
+    assert!(
+        matches!(&errs[..], [Error::InvalidSyntax { message, .. }] if message.contains("too large"))
+    );
+    let errs = eval_err("fn main() { 0b102; }");
+    assert!(
+        matches!(&errs[..], [Error::InvalidSyntax { message, .. }] if message.contains("invalid digit `2`"))
+    );
+}
+
+#[test]
+fn floats() {
+    let val = parse_file("tests/examples/good/floats.zeca");
+    let val = match val {
+        Literal::Array(x) => x,
+        _ => panic!("Returned value is not an Array type"),
+    };
+    match &val[..2] {
+        [Literal::Num(Number::Float(x)), Literal::Num(Number::F32(y))] => {
+            assert!(f64::abs(x - 6.022_140_76e23 * 2.5e-3) < 1e10);
+            assert!(*y == 1f32 / 3.0);
+        }
+        _ => panic!("Returned values are not a Float and a F32"),
+    }
+    assert!(
+        val[2..]
+            == [
+                Literal::Bool(true),
+                Literal::Bool(false),
+                Literal::Bool(true),
+                Literal::Bool(true),
+            ]
+    );
+    let errs = eval_err("fn main() { 1e400; }");
+    assert!(
+        matches!(&errs[..], [Error::InvalidSyntax { message, .. }] if message.contains("out of range"))
+    );
+    let errs = eval_err("fn main() { f64::PI; }");
+    assert!(
+        matches!(&errs[..], [Error::InvalidSyntax { message, .. }] if message.contains("`PI`"))
+    );
+    // Only floats without a suffix adapt to `f32`s
+    let val = zeca::eval_source("fn main() { 1.0 + 1.0f32; }".to_string());
+    assert!(val == Ok(Literal::Num(Number::F32(2.0))));
+    let errs = eval_err("fn main() { 1.0f64 + 1.0f32; }");
+    assert!(
+        matches!(&errs[..], [Error::TypeMismatch { expected, found, .. }] if expected == "`f64`" && found == "f32")
+    );
+    let val = zeca::eval_source("fn main() { -1.5f32; }".to_string());
+    assert!(val == Ok(Literal::Num(Number::F32(-1.5))));
+    let val = zeca::eval_source("fn main() { 5.0 / 0; }".to_string());
+    assert!(matches!(val, Ok(Literal::Num(Number::Float(x))) if x == f64::INFINITY));
+    let val = zeca::eval_source("fn main() { 5.0 % 0; }".to_string());
+    assert!(matches!(val, Ok(Literal::Num(Number::Float(x))) if x.is_nan()));
+    let val = zeca::eval_source("fn main() { 5f32 / 0; }".to_string());
+    assert!(matches!(val, Ok(Literal::Num(Number::F32(x))) if x == f32::INFINITY));
+}
+
+#[test]
+fn comparison() {
+    let val = parse_file("tests/examples/good/comparison.zeca");
+    let expected = [
+        true, true, false, true, true, true, true, false, true, true, true, true, false,
+    ];
+    assert!(val == Literal::Array(expected.into_iter().map(Literal::Bool).collect()));
+    let errs = eval_err(r#"fn main() { 1 == "1"; }"#);
+    assert!(
+        matches!(&errs[..], [Error::TypeMismatch { expected, found, span }] if expected == "a number" && found == "str" && *span == (17..20))
+    );
+    let errs = eval_err("fn main() { true < 1; }");
+    assert!(
+        matches!(&errs[..], [Error::TypeMismatch { expected, found, .. }] if expected == "`bool`" && found == "isize")
+    );
+    let errs = eval_err("fn main() { (0..2) != (0..2); }");
+    assert!(
+        matches!(&errs[..], [Error::TypeMismatch { expected, found, .. }] if expected == "a comparable value" && found == "range")
+    );
+    let errs = eval_err("fn main() { [1, 2] == [1, true]; }");
+    assert!(matches!(&errs[..], [Error::TypeMismatch { .. }]));
+}
+
+#[test]
+fn short_circuit() {
+    let val = parse_file("tests/examples/good/short_circuit.zeca");
+    let expected = [false, true, false, true, true, false];
+    assert!(val == Literal::Array(expected.into_iter().map(Literal::Bool).collect()));
+    let errs = eval_err("fn main() { 1 && true; }");
+    assert!(
+        matches!(&errs[..], [Error::TypeMismatch { expected, found, span }] if expected == "`bool`" && found == "isize" && *span == (12..13))
+    );
+    let errs = eval_err("fn main() { false || 1; }");
+    assert!(matches!(&errs[..], [Error::TypeMismatch { .. }]));
+    // Not reached, so not checked either
+    let val = zeca::eval_source("fn main() { false && 1; }".to_string());
+    assert!(val == Ok(Literal::Bool(false)));
+    // `&&` binds tighter than `||`, as in Rust
+    let val = zeca::eval_source(
+        "fn main() { [true || false && false, false && true || true, false && (true || true)]; }"
+            .to_string(),
+    );
+    let expected = [true, true, false];
+    assert!(
+        val == Ok(Literal::Array(
+            expected.into_iter().map(Literal::Bool).collect()
+        ))
+    );
+}
+
+#[test]
+fn operators() {
+    let val = parse_file("tests/examples/good/operators.zeca");
+    // The last two are cast to `isize`, so they no longer adapt as literals do
+    let expected = [2, -2, 512, 4, 3, 9, 5, -1, 32, -4]
+        .into_iter()
+        .map(Number::Integer)
+        .chain([Number::Isize(15), Number::Isize(0)]);
+    assert!(val == Literal::Array(expected.map(Literal::Num).collect()));
+    let val =
+        zeca::eval_source("fn main() { [!true, true & false, 0xf0u8 >> 4, !0u8]; }".to_string());
+    assert!(
+        val == Ok(Literal::Array(vec![
+            Literal::Bool(false),
+            Literal::Bool(false),
+            Literal::Num(Number::U8(15)),
+            Literal::Num(Number::U8(255)),
+        ]))
+    );
+    let val = zeca::eval_source("fn main() { [2.0 ** 0.5, 7.5 % 2]; }".to_string());
+    assert!(
+        val == Ok(Literal::Array(vec![
+            Literal::Num(Number::Float(2f64.powf(0.5))),
+            Literal::Num(Number::Float(1.5)),
+        ]))
+    );
+    let errs = eval_err("fn main() { 1 % 0; }");
+    assert!(matches!(&errs[..], [Error::DivisionByZero { .. }]));
+    let errs = eval_err("fn main() { 2u8 ** 8; }");
+    assert!(matches!(
+        &errs[..],
+        [Error::Overflow {
+            operation: "raise to a power",
+            ..
+        }]
+    ));
+    // Exponents and shift amounts are `u32`s, as in Rust, so negative ones are out of range rather than overflowing
+    let errs = eval_err("fn main() { 2 ** -1; }");
+    assert!(matches!(
+        &errs[..],
+        [Error::OperandOutOfRange { operand: "exponent", value, span }] if value == "-1" && *span == (17..19)
+    ));
+    assert!(errs[0].to_string() == "exponent `-1` is out of range for `u32`");
+    let errs = eval_err("fn main() { 1 ** 4294967296; }");
+    assert!(matches!(
+        &errs[..],
+        [Error::OperandOutOfRange {
+            operand: "exponent",
+            ..
+        }]
+    ));
+    let errs = eval_err("fn main() { 1u8 << 8; }");
+    assert!(matches!(
+        &errs[..],
+        [Error::Overflow {
+            operation: "shift left",
+            ..
+        }]
+    ));
+    let errs = eval_err("fn main() { 1 >> 64; }");
+    assert!(matches!(
+        &errs[..],
+        [Error::Overflow {
+            operation: "shift right",
+            ..
+        }]
+    ));
+    let errs = eval_err("fn main() { 1 >> -1; }");
+    assert!(matches!(
+        &errs[..],
+        [Error::OperandOutOfRange {
+            operand: "shift amount",
+            ..
+        }]
+    ));
+    let errs = eval_err("fn main() { 1 << -1; }");
+    assert!(matches!(
+        &errs[..],
+        [Error::OperandOutOfRange {
+            operand: "shift amount",
+            ..
+        }]
+    ));
+    let errs = eval_err("fn main() { 1.5 & 1; }");
+    assert!(
+        matches!(&errs[..], [Error::TypeMismatch { expected, found, span }] if expected == "an integer" && found == "f64" && *span == (12..15))
+    );
+    let errs = eval_err("fn main() { true | 1; }");
+    assert!(
+        matches!(&errs[..], [Error::TypeMismatch { expected, found, .. }] if expected == "`bool`" && found == "isize")
+    );
+    let errs = eval_err("fn main() { !1.5; }");
+    assert!(matches!(&errs[..], [Error::TypeMismatch { found, .. }] if found == "f64"));
+}
+
+#[test]
+fn compound_assign() {
+    let val = parse_file("tests/examples/good/compound_assign.zeca");
+    let expected = [55, 4, 8, 1, 0b1010, 40];
+    assert!(
+        val == Literal::Array(
+            expected
+                .into_iter()
+                .map(|x| Literal::Num(Number::Integer(x)))
+                .collect()
+        )
+    );
+    let errs = eval_err("fn main() { let mut x = 250u8; x += 10; }");
+    assert!(matches!(
+        &errs[..],
+        [Error::Overflow {
+            operation: "add",
+            span
+        }] if *span == (31..38)
+    ));
+    let errs = eval_err("fn main() { let mut x = true; x -= 1; }");
+    assert!(
+        matches!(&errs[..], [Error::TypeMismatch { found, span, .. }] if found == "bool" && *span == (30..31))
+    );
+    let errs = eval_err("fn main() { y += 1; }");
+    assert!(
+        matches!(&errs[..], [Error::UndefinedVariable { name, span }] if name == "y" && *span == (12..13))
+    );
+    let errs = eval_err("fn main() { let mut x = 1; x /= 0; }");
+    assert!(matches!(&errs[..], [Error::DivisionByZero { .. }]));
+}
+
+#[test]
+fn index_assign() {
+    let val = parse_file("tests/examples/good/index_assign.zeca");
+    let row = |xs: [isize; 3]| {
+        Literal::Array(
+            xs.into_iter()
+                .map(|x| Literal::Num(Number::Integer(x)))
+                .collect(),
+        )
+    };
+    assert!(
+        val == Literal::Array(vec![
+            row([10, 2, 33]),
+            Literal::Array(vec![row([1, 0, 0]), row([0, 7, 0]), row([0, 0, 1])]),
+        ])
+    );
+    let errs = eval_err("fn main() { let mut arr = [1, 2]; arr[2] = 0; }");
+    assert!(matches!(
+        &errs[..],
+        [Error::IndexOutOfBounds { index: 2, len: 2, span }] if *span == (34..40)
+    ));
+    let errs = eval_err("fn main() { let mut m = [[1], [2]]; m[1][1] += 1; }");
+    assert!(matches!(
+        &errs[..],
+        [Error::IndexOutOfBounds { index: 1, len: 1, span }] if *span == (36..43)
+    ));
+    let errs = eval_err("fn main() { let mut x = 1; x[0] = 0; }");
+    assert!(
+        matches!(&errs[..], [Error::TypeMismatch { expected, found, span }] if expected == "an array" && found == "isize" && *span == (27..28))
+    );
+    let errs = eval_err("fn main() { let mut arr = [1]; arr[-1] = 0; }");
+    assert!(matches!(&errs[..], [Error::NegativeIndex { span }] if *span == (35..37)));
+    let errs = eval_err("fn main() { let mut arr = [1]; arr[true] = 0; }");
+    assert!(matches!(&errs[..], [Error::TypeMismatch { found, .. }] if found == "bool"));
+    let errs = eval_err("fn main() { arr[0] = 0; }");
+    assert!(matches!(&errs[..], [Error::UndefinedVariable { name, .. }] if name == "arr"));
+}
+
+#[test]
+fn number_errors() {
+    let errs = eval_err("fn main() { 1 / 0; }");
+    assert!(matches!(&errs[..], [Error::DivisionByZero { .. }]));
+    let errs = eval_err("fn main() { 9223372036854775807 * 2; }");
+    assert!(matches!(
+        &errs[..],
+        [Error::Overflow {
+            operation: "multiply",
+            ..
+        }]
+    ));
+    let errs = eval_err("fn main() { -9223372036854775807 - 2; }");
+    assert!(matches!(
+        &errs[..],
+        [Error::Overflow {
+            operation: "subtract",
+            ..
+        }]
+    ));
+    let errs = eval_err(r#"fn main() { "1" as isize; }"#);
+    assert!(matches!(&errs[..], [Error::TypeMismatch { found, .. }] if found == "str"));
+    let errs = eval_err("fn main() { 1.5 as bool; }");
+    assert!(matches!(&errs[..], [Error::InvalidSyntax { .. }]));
+}
+
+#[test]
+fn no_panics() {
+    let errs = eval_err("fn main() { true < 1; }");
+    assert!(matches!(&errs[..], [Error::TypeMismatch { found, .. }] if found == "isize"));
+    let errs = eval_err("fn main() { 9223372036854775807 + 1; }");
+    assert!(matches!(
+        &errs[..],
+        [Error::Overflow {
+            operation: "add",
+            ..
+        }]
+    ));
+    let errs = eval_err("fn main() { -(-9223372036854775807 - 1); }");
+    assert!(matches!(
+        &errs[..],
+        [Error::Overflow {
+            operation: "negate",
+            ..
+        }]
+    ));
+    let errs = eval_err("fn main() { (-9223372036854775807 - 1) / -1; }");
+    assert!(matches!(
+        &errs[..],
+        [Error::Overflow {
+            operation: "divide",
+            ..
+        }]
+    ));
+    let errs = eval_err("fn main() { 99999999999999999999; }");
+    assert!(matches!(&errs[..], [Error::InvalidSyntax { .. }]));
+    let errs = eval_err("fn id(x) { x; } fn main() { id(y); }");
+    assert!(matches!(&errs[..], [Error::UndefinedVariable { name, .. }] if name == "y"));
+    let errs = eval_err("fn main() { main(); }");
+    assert!(matches!(&errs[..], [Error::RecursionLimit { .. }]));
+    // Calls under the recursion limit can still nest deep enough to overflow the stack
+    let src = format!(
+        "fn f(k) {{ if k == 0 {{ 0; }} else {{ {} f(k - 1); {} }} }} fn main() {{ f(999); }}",
+        "{".repeat(300),
+        "}".repeat(300)
+    );
+    let errs = eval_err(&src);
+    assert!(matches!(
+        &errs[..],
+        [Error::NestingLimit { limit: 10000, .. }]
+    ));
+    let src = format!(
+        "fn f(k) {{ if k == 0 {{ 0; }} else {{ {}f(k - 1){}; }} }} fn main() {{ f(999); }}",
+        "(1 + ".repeat(300),
+        ")".repeat(300)
+    );
+    let errs = eval_err(&src);
+    assert!(matches!(&errs[..], [Error::NestingLimit { .. }]));
+    // Widths and precisions big enough to overflow or exhaust memory are rejected
+    for src in [
+        r#"fn main() { format!("{:.70000}", 1.5); }"#,
+        r#"fn main() { format!("{:99999999999999}", 1); }"#,
+        r#"fn main() { format!("{:99999999999999999999999}", 1); }"#,
+    ] {
+        let errs = eval_err(src);
+        assert!(matches!(&errs[..], [Error::InvalidSyntax { .. }]));
+    }
+    for src in [
+        r#"fn main() { format!("{:.*}", 100000, 1.5); }"#,
+        r#"fn main() { let w = 9999999999999; format!("{:w$}", 1); }"#,
+        r#"fn main() { format!("{:1$}", 1, 65536); }"#,
+    ] {
+        let errs = eval_err(src);
+        assert!(matches!(
+            &errs[..],
+            [Error::CountTooLarge { limit: 65535, .. }]
+        ));
+    }
+    let val = zeca::eval_source(r#"fn main() { format!("{:65535}", 1).len(); }"#.to_string());
+    assert!(val == Ok(Literal::Num(Number::UInteger(65535))));
+}
+
+/// Tokens the fuzzer builds programs from. Loop keywords are left out, as random loops would seldom stop
+const FUZZ_TOKENS: &[&str] = &[
+    "let",
+    "x",
+    "y",
+    "=",
+    "+=",
+    ";",
+    ",",
+    ":",
+    "0",
+    "1",
+    "2.5",
+    "9223372036854775807",
+    "-",
+    "+",
+    "*",
+    "/",
+    "%",
+    "**",
+    "!",
+    "&",
+    "|",
+    "^",
+    "<<",
+    ">>",
+    "==",
+    "!=",
+    "<",
+    ">",
+    "<=",
+    ">=",
+    "&&",
+    "||",
+    "..",
+    "..=",
+    "(",
+    ")",
+    "[",
+    "]",
+    "{",
+    "}",
+    "true",
+    "false",
+    "\"str\"",
+    "\"\\n\"",
+    "'c'",
+    "if",
+    "else",
+    "return",
+    "break",
+    "continue",
+    "'a",
+    "fn",
+    "f",
+    "f(1)",
+    "f(x)",
+    ".len()",
+    "format!(\"{x:>3}\")",
+    "format!(\"{:.*}\", x, 1.5)",
+    "format!(\"{:x$}\", 1)",
+    "[0..1]",
+];
+
+#[test]
+fn nesting_limits() {
+    // Sources nested too deeply to parse are rejected up front, with where the limit was passed
+    for (open, close) in [("(", ")"), ("-", ""), ("!", ""), ("{ ", " }"), ("|| ", "")] {
+        let src = format!(
+            "fn main() {{ {}1{}; }}",
+            open.repeat(100_000),
+            close.repeat(100_000)
+        );
+        let errs = eval_err(&src);
+        assert!(matches!(
+            &errs[..],
+            [Error::NestingLimit { limit: zeca::PARSE_NESTING_LIMIT, span }] if span.start > 12
+        ));
+    }
+    let src = format!("fn main() {{ {}1{}; }}", "-".repeat(200_000), "");
+    let errs = eval_err(&src);
+    assert!(matches!(&errs[..], [Error::NestingLimit { .. }]));
+    // Up to the limit they still parse and evaluate
+    let src = format!("fn main() {{ {}-1{}; }}", "(".repeat(990), ")".repeat(990));
+    assert_eq!(
+        zeca::eval_source(src).unwrap(),
+        Literal::Num(Number::Integer(-1))
+    );
+    // Brackets and operators in strings, chars and comments aren't nesting
+    let src = format!(
+        "fn main() {{ // {0}\n /* {0} */ let s = \"{0}\"; let c = '('; let l = '-'; s.len(); }}",
+        "(-".repeat(100_000)
+    );
+    assert_eq!(
+        zeca::eval_source(src).unwrap(),
+        Literal::Num(Number::UInteger(200_000))
+    );
+    // Long chains of operators aren't nested brackets, but are too deep to evaluate
+    let src = format!("fn main() {{ {}1; }}", "1 + ".repeat(50_000));
+    let errs = eval_err(&src);
+    assert!(matches!(
+        &errs[..],
+        [Error::NestingLimit { limit: 10000, .. }]
+    ));
+}
+
+#[test]
+fn fuzz() {
+    // Xorshift, so every run tries the same programs
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as usize
+    };
+    for _ in 0..2000 {
+        let tokens: Vec<_> = (0..next() % 24)
+            .map(|_| FUZZ_TOKENS[next() % FUZZ_TOKENS.len()])
+            .collect();
+        let src = format!("fn f(x) {{ x * 2; }} fn main() {{ {} }}", tokens.join(" "));
+        // Any result is fine, as long as it is returned instead of panicking
+        let _ = zeca::eval_source(src);
+    }
+}
+
+#[test]
+fn error_spans() {
+    // Spans are in characters, and point at the offending part of the source
+    let src = "fn main() {\n    let y = 1;\n    y + x;\n}";
+    let errs = eval_err(src);
+    let span = errs[0].span();
+    assert_eq!(&src[span], "x");
+    let src = "fn main() { if 1 + 2 { 3; } }";
+    let errs = eval_err(src);
+    assert_eq!(&src[errs[0].span()], "1 + 2");
+    let src = "fn main() { let a = 4 / (2 - 2); }";
+    let errs = eval_err(src);
+    assert_eq!(&src[errs[0].span()], "4 / (2 - 2)");
+    let src = "fn main() { while true { break 5; } }";
+    let errs = eval_err(src);
+    assert_eq!(&src[errs[0].span()], "5");
+    let src = "fn main() { continue; }";
+    let errs = eval_err(src);
+    assert_eq!(&src[errs[0].span()], "continue;");
+}
+
+#[test]
+fn error_reports() {
+    let src = "fn main() {\n    let y = 1;\n    y + x;\n}";
+    let mut output = Vec::new();
+    zeca::report::write_reports(&eval_err(src), "test.zeca", src, false, &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    println!("{}", output);
+    assert!(output.contains("cannot find variable `x` in this scope"));
+    // File name, line and column of the error
+    assert!(output.contains("test.zeca:3:9"));
+    // Offending line, with the label
+    assert!(output.contains("    y + x;"));
+    assert!(output.contains("not found in this scope"));
+    // Colors are only used when asked for
+    assert!(!output.contains('\u{1b}'));
+
+    let src = "fn main() { while true { break 1; } }";
+    let mut output = Vec::new();
+    zeca::report::write_reports(&eval_err(src), "test.zeca", src, true, &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("`while` and `for` loops always evaluate to `()`"));
+    assert!(output.contains('\u{1b}'));
+}
+
+#[test]
+fn parse_errors() {
+    let errs = eval_err("fn main() { 1 + ; }");
+    assert!(!errs.is_empty());
+    for err in errs {
+        assert!(matches!(err, Error::Parse { .. }));
+        // Errors are displayed as messages, not as their Debug representation
+        assert!(err.to_string().starts_with("unexpected"));
+    }
+}
+
 #[test]
 fn conditional() {
     let expected_value = 5;